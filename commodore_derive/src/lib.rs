@@ -0,0 +1,210 @@
+//! Derives `commodore::CommandArgs` for structs whose fields describe
+//! a command's arguments
+//!
+//! Fields are read as positional arguments in declaration order unless annotated.
+//! `#[arg(long)]` reads a field from a `--field-name <value>` option and
+//! `#[arg(flag)]` sets a `bool` field when `--field-name` is present. An alternative
+//! option name may be provided with `#[arg(long = "--name")]`. `Option` fields are
+//! optional and may only be followed by other optional positional fields. A trailing
+//! `Vec` field collects any remaining positional arguments. Options may not be `Vec`s.
+//! Field types are parsed with `FromStr`
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{Body, Field, Ident, Lit, MetaItem, NestedMetaItem, Ty, VariantData};
+
+#[proc_macro_derive(CommandArgs, attributes(arg))]
+pub fn derive_command_args(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+    let gen = impl_command_args(&ast);
+    gen.parse().unwrap()
+}
+
+/// How a field is read from arguments
+enum Kind {
+    Positional,
+    Long(String),
+    Flag(String),
+}
+
+/// The shape of a field's type
+enum Shape {
+    Required,
+    Optional,
+    Repeated,
+}
+
+fn option_name(field: &Ident) -> String {
+    format!("--{}", field.as_ref().replace('_', "-"))
+}
+
+fn kind(field: &Field) -> Kind {
+    let ident = field.ident.as_ref().unwrap();
+    let mut kind = Kind::Positional;
+    for attr in field.attrs.iter() {
+        if let MetaItem::List(ref name, ref items) = attr.value {
+            if name != "arg" {
+                continue;
+            }
+            for item in items {
+                kind = match *item {
+                    NestedMetaItem::MetaItem(MetaItem::Word(ref word)) if word == "long" => {
+                        Kind::Long(option_name(ident))
+                    }
+                    NestedMetaItem::MetaItem(MetaItem::Word(ref word)) if word == "flag" => {
+                        Kind::Flag(option_name(ident))
+                    }
+                    NestedMetaItem::MetaItem(
+                        MetaItem::NameValue(ref word, Lit::Str(ref value, _)),
+                    ) if word == "long" => Kind::Long(value.clone()),
+                    NestedMetaItem::MetaItem(
+                        MetaItem::NameValue(ref word, Lit::Str(ref value, _)),
+                    ) if word == "flag" => Kind::Flag(value.clone()),
+                    _ => panic!("unsupported #[arg] attribute on field `{}`", ident),
+                }
+            }
+        }
+    }
+    kind
+}
+
+fn shape(ty: &Ty) -> Shape {
+    if let Ty::Path(None, ref path) = *ty {
+        if let Some(segment) = path.segments.last() {
+            if segment.ident == "Option" {
+                return Shape::Optional;
+            }
+            if segment.ident == "Vec" {
+                return Shape::Repeated;
+            }
+        }
+    }
+    Shape::Required
+}
+
+fn is_bool(ty: &Ty) -> bool {
+    match *ty {
+        Ty::Path(None, ref path) => path.segments.len() == 1 && path.segments[0].ident == "bool",
+        _ => false,
+    }
+}
+
+fn impl_command_args(ast: &syn::DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let fields = match ast.body {
+        Body::Struct(VariantData::Struct(ref fields)) => fields,
+        _ => panic!("#[derive(CommandArgs)] is only supported for structs with named fields"),
+    };
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let mut flags = vec![];
+    let mut options = vec![];
+    let mut reads = vec![];
+    let mut usage = vec![];
+    // the Vec field collecting remaining positional arguments, if any
+    let mut rest: Option<&Ident> = None;
+    // the last optional positional field, if any
+    let mut optional: Option<&Ident> = None;
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let (kind, shape) = (kind(field), shape(&field.ty));
+        if let Kind::Positional = kind {
+            if let Some(rest) = rest {
+                panic!(
+                    "field `{}` can never be read because `{}` collects all remaining \
+                     positional arguments. Move `{}` after it",
+                    ident,
+                    rest,
+                    rest
+                );
+            }
+            match shape {
+                Shape::Repeated => rest = Some(ident),
+                Shape::Optional => optional = Some(ident),
+                Shape::Required => {
+                    if let Some(optional) = optional {
+                        panic!(
+                            "field `{}` can never be read without `{}`, which is optional. \
+                             Move `{}` after it",
+                            ident,
+                            optional,
+                            optional
+                        );
+                    }
+                }
+            }
+        }
+        let read = match (kind, shape) {
+            (Kind::Flag(_), _) if !is_bool(&field.ty) => {
+                panic!("#[arg(flag)] requires field `{}` to be a bool", ident)
+            }
+            (Kind::Flag(option), _) => {
+                usage.push(format!("[{}]", option));
+                let read = quote! { parser.flag(#option) };
+                flags.push(option);
+                read
+            }
+            (Kind::Long(_), Shape::Repeated) => {
+                panic!(
+                    "repeated options are not supported. #[arg(long)] field `{}` may not be a Vec",
+                    ident
+                )
+            }
+            (Kind::Long(option), Shape::Optional) => {
+                usage.push(format!("[{} <{}>]", option, ident));
+                let read = quote! { parser.option(#option)? };
+                options.push(option);
+                read
+            }
+            (Kind::Long(option), _) => {
+                usage.push(format!("{} <{}>", option, ident));
+                let read = quote! { parser.required_option(#option)? };
+                options.push(option);
+                read
+            }
+            (Kind::Positional, Shape::Optional) => {
+                let label = ident.as_ref();
+                usage.push(format!("[{}]", label));
+                quote! { parser.optional(#label)? }
+            }
+            (Kind::Positional, Shape::Repeated) => {
+                let label = ident.as_ref();
+                usage.push(format!("[{}...]", label));
+                quote! { parser.rest(#label)? }
+            }
+            (Kind::Positional, Shape::Required) => {
+                let label = ident.as_ref();
+                usage.push(format!("<{}>", label));
+                quote! { parser.positional(#label)? }
+            }
+        };
+        reads.push(quote! { let #ident = #read; });
+    }
+    let idents = fields.iter().map(|field| field.ident.as_ref().unwrap());
+    let values = fields.iter().map(|field| field.ident.as_ref().unwrap());
+    let usage = usage.join(" ");
+
+    quote! {
+        impl #impl_generics ::commodore::CommandArgs for #name #ty_generics #where_clause {
+            fn from_args(args: &::commodore::Args) -> ::commodore::errors::Result<Self> {
+                #[allow(unused_mut)]
+                let mut parser = ::commodore::ArgsParser::new(
+                    args,
+                    &[#(#flags),*],
+                    &[#(#options),*]
+                )?;
+                #(#reads)*
+                parser.finish()?;
+                Ok(#name { #(#idents: #values),* })
+            }
+
+            fn usage() -> String {
+                #usage.to_owned()
+            }
+        }
+    }
+}
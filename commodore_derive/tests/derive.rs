@@ -0,0 +1,107 @@
+extern crate commodore;
+#[macro_use]
+extern crate commodore_derive;
+
+use commodore::{Command, CommandArgs, Response};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+enum Env {
+    Staging,
+    Prod,
+}
+
+impl FromStr for Env {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Env, String> {
+        match s {
+            "staging" => Ok(Env::Staging),
+            "prod" => Ok(Env::Prod),
+            other => Err(format!("expected staging or prod but got {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, CommandArgs)]
+struct Deploy {
+    service: String,
+    #[arg(long)]
+    env: Env,
+    #[arg(flag)]
+    force: bool,
+    #[arg(long = "--max-replicas")]
+    replicas: Option<u32>,
+    hosts: Vec<String>,
+}
+
+fn command(text: &str) -> Command {
+    Command {
+        command: "/deploy".to_owned(),
+        text: text.to_owned(),
+        ..Default::default()
+    }
+}
+
+fn error(result: Result<Deploy, Response>) -> String {
+    match result {
+        Err(response) => response.text.unwrap_or_default(),
+        Ok(deploy) => panic!("expected an error but got {:?}", deploy),
+    }
+}
+
+#[test]
+fn generates_usage() {
+    assert_eq!(
+        Deploy::usage(),
+        "<service> --env <env> [--force] [--max-replicas <replicas>] [hosts...]"
+    );
+}
+
+#[test]
+fn parses_arguments() {
+    assert_eq!(
+        command("api web1 --env prod web2 --force --max-replicas=3").parse::<Deploy>(),
+        Ok(Deploy {
+            service: "api".to_owned(),
+            env: Env::Prod,
+            force: true,
+            replicas: Some(3),
+            hosts: vec!["web1".to_owned(), "web2".to_owned()],
+        })
+    );
+    assert_eq!(
+        command("\"api\" --env=staging").parse::<Deploy>(),
+        Ok(Deploy {
+            service: "api".to_owned(),
+            env: Env::Staging,
+            force: false,
+            replicas: None,
+            hosts: vec![],
+        })
+    );
+}
+
+#[test]
+fn replies_with_usage_errors() {
+    let usage = "usage: `/deploy <service> --env <env> [--force] [--max-replicas <replicas>] [hosts...]`";
+    assert_eq!(
+        error(command("--env prod").parse()),
+        format!("missing <service>\n{}", usage)
+    );
+    assert_eq!(
+        error(command("api").parse()),
+        format!("missing `--env`\n{}", usage)
+    );
+    assert_eq!(
+        error(command("api --env dev").parse()),
+        format!(
+            "invalid value `dev` for --env: expected staging or prod but got dev\n{}",
+            usage
+        )
+    );
+    assert_eq!(
+        error(command("api --env prod --verbose").parse()),
+        format!("unknown option `--verbose`\n{}", usage)
+    );
+}
@@ -5,7 +5,7 @@ extern crate env_logger;
 extern crate commodore;
 extern crate hyper;
 
-use commodore::{Captures, Command, Mux, Responder, Response};
+use commodore::{Command, Mux, Params, Responder, Response};
 use hyper::Server;
 use std::thread;
 use std::time::Duration;
@@ -16,7 +16,7 @@ pub fn main() {
     mux.command(
         "/commodore",
         "secrettoken",
-        |c: &Command, _: &Option<Captures>, responder: Box<Responder>| -> Option<Response> {
+        |c: &Command, _: &Params, responder: Box<Responder>| -> Option<Response> {
             info!("handler recv cmd {:#?}", c);
             thread::spawn(
                 move || {
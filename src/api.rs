@@ -0,0 +1,620 @@
+//! A minimal [Slack Web API](https://api.slack.com/web) client
+//!
+//! Commands may only be answered via `response_url` for a short
+//! while after they are issued. Beyond that, handlers may talk to Slack
+//! using a bot token and the methods provided here.
+
+use super::Responder;
+use errors::{Error, ErrorKind, Result};
+use hyper::Client as HyperClient;
+use hyper::header::{Authorization, Bearer, ContentType};
+use hyper::net::HttpsConnector;
+use hyper::status::StatusCode;
+use hyper_native_tls::NativeTlsClient;
+use ids::{ChannelId, EnterpriseId, TeamId, UserGroupId, UserId};
+use rbac::UserGroupMembers;
+use response::{Attachment, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+use std::io::Read;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use url::form_urlencoded;
+
+const DEFAULT_BASE_URL: &'static str = "https://slack.com/api";
+const DEFAULT_RETRIES: usize = 3;
+const RESPONDER_METHODS: &'static [Method] = &[Method::ChatPostMessage, Method::ChatPostEphemeral];
+
+/// Slack Web API methods supported by this client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    ChatPostMessage,
+    ChatPostEphemeral,
+    ChatUpdate,
+    ChatDelete,
+    UsersInfo,
+    ConversationsInfo,
+    UserGroupsUsersList,
+    OAuthV2Access,
+}
+
+impl Method {
+    /// the method name as it appears in api urls
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Method::ChatPostMessage => "chat.postMessage",
+            Method::ChatPostEphemeral => "chat.postEphemeral",
+            Method::ChatUpdate => "chat.update",
+            Method::ChatDelete => "chat.delete",
+            Method::UsersInfo => "users.info",
+            Method::ConversationsInfo => "conversations.info",
+            Method::UserGroupsUsersList => "usergroups.users.list",
+            Method::OAuthV2Access => "oauth.v2.access",
+        }
+    }
+
+    /// the bot token scopes required to call this method. `conversations.info` requires a
+    /// read scope for each kind of conversation it is asked about, so all four are requested
+    pub fn scopes(&self) -> &'static [&'static str] {
+        match *self {
+            Method::ChatPostMessage |
+            Method::ChatPostEphemeral |
+            Method::ChatUpdate |
+            Method::ChatDelete => &["chat:write"],
+            Method::UsersInfo => &["users:read"],
+            Method::ConversationsInfo => &["channels:read", "groups:read", "im:read", "mpim:read"],
+            Method::UserGroupsUsersList => &["usergroups:read"],
+            Method::OAuthV2Access => &[],
+        }
+    }
+}
+
+/// A posted or updated message
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Message {
+    pub channel: ChannelId,
+    pub ts: String,
+}
+
+/// A posted ephemeral message
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Ephemeral {
+    pub message_ts: String,
+}
+
+/// A Slack user. See [user type](https://api.slack.com/types/user)
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct User {
+    pub id: UserId,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub real_name: Option<String>,
+    #[serde(default)]
+    pub team_id: Option<TeamId>,
+    #[serde(default)]
+    pub tz: Option<String>,
+    #[serde(default)]
+    pub is_bot: bool,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// A Slack conversation. See [conversation type](https://api.slack.com/types/conversation)
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Conversation {
+    pub id: ChannelId,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub is_channel: bool,
+    #[serde(default)]
+    pub is_group: bool,
+    #[serde(default)]
+    pub is_im: bool,
+    #[serde(default)]
+    pub is_private: bool,
+    #[serde(default)]
+    pub is_archived: bool,
+}
+
+/// A team reference included in oauth responses
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Team {
+    pub id: TeamId,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// An enterprise reference included in oauth responses
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Enterprise {
+    pub id: EnterpriseId,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// The user that authorized an installation
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct AuthedUser {
+    pub id: UserId,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub access_token: Option<String>,
+}
+
+/// The result of exchanging an oauth code.
+/// See [oauth.v2.access](https://api.slack.com/methods/oauth.v2.access)
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct OAuthAccess {
+    pub access_token: String,
+    #[serde(default)]
+    pub token_type: Option<String>,
+    #[serde(default)]
+    pub scope: String,
+    #[serde(default)]
+    pub bot_user_id: Option<UserId>,
+    #[serde(default)]
+    pub app_id: Option<String>,
+    #[serde(default)]
+    pub team: Option<Team>,
+    #[serde(default)]
+    pub enterprise: Option<Enterprise>,
+    #[serde(default)]
+    pub is_enterprise_install: bool,
+    #[serde(default)]
+    pub authed_user: Option<AuthedUser>,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    user: User,
+}
+
+#[derive(Deserialize)]
+struct ConversationInfo {
+    channel: Conversation,
+}
+
+#[derive(Deserialize)]
+struct UserGroupUsers {
+    users: Vec<UserId>,
+}
+
+#[derive(Serialize)]
+struct MessagePayload<'a> {
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: &'a Vec<Attachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<&'a str>,
+}
+
+impl<'a> MessagePayload<'a> {
+    fn new(channel: &'a str, response: &'a Response) -> MessagePayload<'a> {
+        MessagePayload {
+            channel: channel,
+            text: response.text.as_ref().map(|t| &t[..]),
+            attachments: &response.attachments,
+            user: None,
+            ts: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeletePayload<'a> {
+    channel: &'a str,
+    ts: &'a str,
+}
+
+enum Body {
+    Json(String),
+    Form(String),
+}
+
+/// A Slack Web API client authenticated with a bot token
+pub struct Client {
+    token: String,
+    base_url: String,
+    retries: usize,
+    http: HyperClient,
+}
+
+impl Client {
+    /// Creates a new client for the provided bot token
+    pub fn new<T>(token: T) -> Client
+    where
+        T: Into<String>,
+    {
+        Client::with_base_url(token, DEFAULT_BASE_URL)
+    }
+
+    /// Creates a new client without a bot token. This is useful for
+    /// methods which authenticate with app credentials, like `oauth_v2_access`
+    pub fn anonymous() -> Client {
+        Client::new("")
+    }
+
+    /// Creates a new client which sends requests to an alternative
+    /// base url. This is useful for testing against a local stand-in server
+    pub fn with_base_url<T, U>(token: T, base_url: U) -> Client
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        Client {
+            token: token.into(),
+            base_url: base_url.into().trim_right_matches('/').to_owned(),
+            retries: DEFAULT_RETRIES,
+            http: HyperClient::with_connector(HttpsConnector::new(NativeTlsClient::new().unwrap())),
+        }
+    }
+
+    /// Sets the number of times a rate limited request will be retried
+    /// before failing with `ErrorKind::RateLimited`
+    pub fn retries(&mut self, retries: usize) -> &mut Client {
+        self.retries = retries;
+        self
+    }
+
+    /// Posts a message to a channel. See [chat.postMessage](https://api.slack.com/methods/chat.postMessage)
+    pub fn post_message(&self, channel: &ChannelId, response: &Response) -> Result<Message> {
+        self.json(
+            Method::ChatPostMessage,
+            &MessagePayload::new(channel.as_str(), response),
+        )
+    }
+
+    /// Posts a message visible only to the provided user.
+    /// See [chat.postEphemeral](https://api.slack.com/methods/chat.postEphemeral)
+    pub fn post_ephemeral(
+        &self,
+        channel: &ChannelId,
+        user: &UserId,
+        response: &Response,
+    ) -> Result<Ephemeral> {
+        self.json(
+            Method::ChatPostEphemeral,
+            &MessagePayload {
+                user: Some(user.as_str()),
+                ..MessagePayload::new(channel.as_str(), response)
+            },
+        )
+    }
+
+    /// Replaces the contents of a previously posted message.
+    /// See [chat.update](https://api.slack.com/methods/chat.update)
+    pub fn update<T>(&self, channel: &ChannelId, ts: T, response: &Response) -> Result<Message>
+    where
+        T: AsRef<str>,
+    {
+        self.json(
+            Method::ChatUpdate,
+            &MessagePayload {
+                ts: Some(ts.as_ref()),
+                ..MessagePayload::new(channel.as_str(), response)
+            },
+        )
+    }
+
+    /// Deletes a previously posted message.
+    /// See [chat.delete](https://api.slack.com/methods/chat.delete)
+    pub fn delete<T>(&self, channel: &ChannelId, ts: T) -> Result<Message>
+    where
+        T: AsRef<str>,
+    {
+        self.json(
+            Method::ChatDelete,
+            &DeletePayload {
+                channel: channel.as_str(),
+                ts: ts.as_ref(),
+            },
+        )
+    }
+
+    /// Fetches information about a user. See [users.info](https://api.slack.com/methods/users.info)
+    pub fn user_info(&self, user: &UserId) -> Result<User> {
+        self.form::<UserInfo>(Method::UsersInfo, &[("user", user.as_str())])
+            .map(|info| info.user)
+    }
+
+    /// Fetches information about a conversation.
+    /// See [conversations.info](https://api.slack.com/methods/conversations.info)
+    pub fn conversation_info(&self, channel: &ChannelId) -> Result<Conversation> {
+        self.form::<ConversationInfo>(Method::ConversationsInfo, &[("channel", channel.as_str())])
+            .map(|info| info.channel)
+    }
+
+    /// Lists the ids of the members of a user group.
+    /// See [usergroups.users.list](https://api.slack.com/methods/usergroups.users.list)
+    pub fn usergroup_members(&self, usergroup: &UserGroupId) -> Result<Vec<UserId>> {
+        self.form::<UserGroupUsers>(
+            Method::UserGroupsUsersList,
+            &[("usergroup", usergroup.as_str())],
+        )
+            .map(|list| list.users)
+    }
+
+    /// Exchanges a temporary oauth code for an access token.
+    /// See [oauth.v2.access](https://api.slack.com/methods/oauth.v2.access)
+    pub fn oauth_v2_access(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        code: &str,
+        redirect_uri: Option<&str>,
+    ) -> Result<OAuthAccess> {
+        let mut params = vec![
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("code", code),
+        ];
+        if let Some(uri) = redirect_uri {
+            params.push(("redirect_uri", uri));
+        }
+        self.form(Method::OAuthV2Access, &params)
+    }
+
+    fn json<P, T>(&self, method: Method, payload: &P) -> Result<T>
+    where
+        P: Serialize,
+        T: Deserialize,
+    {
+        let body = serde_json::to_string(payload)?;
+        self.call(method, Body::Json(body))
+    }
+
+    fn form<T>(&self, method: Method, params: &[(&str, &str)]) -> Result<T>
+    where
+        T: Deserialize,
+    {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        self.call(method, Body::Form(body))
+    }
+
+    fn call<T>(&self, method: Method, body: Body) -> Result<T>
+    where
+        T: Deserialize,
+    {
+        let url = format!("{}/{}", self.base_url, method.name());
+        let (content_type, body) = match body {
+            Body::Json(body) => (ContentType::json(), body),
+            Body::Form(body) => (ContentType::form_url_encoded(), body),
+        };
+        let mut attempts = 0;
+        loop {
+            let mut req = self.http.post(&url[..]).header(content_type.clone());
+            if !self.token.is_empty() {
+                req = req.header(Authorization(Bearer { token: self.token.clone() }));
+            }
+            let mut res = req.body(body.as_bytes()).send()?;
+            if res.status == StatusCode::TooManyRequests {
+                let retry_after = res.headers
+                    .get_raw("Retry-After")
+                    .and_then(|values| values.first())
+                    .and_then(|value| String::from_utf8_lossy(value).trim().parse::<u64>().ok());
+                if attempts >= self.retries {
+                    return Err(ErrorKind::RateLimited(retry_after).into());
+                }
+                attempts += 1;
+                debug!(
+                    "{} rate limited. retrying in {:?} seconds",
+                    method.name(),
+                    retry_after
+                );
+                thread::sleep(Duration::from_secs(retry_after.unwrap_or(1)));
+                continue;
+            }
+            let mut buffer = String::new();
+            res.read_to_string(&mut buffer)?;
+            return parse(&buffer);
+        }
+    }
+}
+
+impl UserGroupMembers for Client {
+    fn members(&self, usergroup_id: &UserGroupId) -> Result<Vec<UserId>> {
+        self.usergroup_members(usergroup_id)
+    }
+}
+
+/// A Responder which replies with Web API methods rather than a command's
+/// `response_url`. This is useful for deferred responses which may outlive
+/// the `response_url`. Ephemeral responses are posted to the provided user and
+/// in channel responses are posted to the channel
+pub struct ApiResponder {
+    client: Arc<Client>,
+    channel: ChannelId,
+    user: UserId,
+}
+
+impl ApiResponder {
+    pub fn new(client: Arc<Client>, channel: ChannelId, user: UserId) -> ApiResponder {
+        ApiResponder {
+            client: client,
+            channel: channel,
+            user: user,
+        }
+    }
+
+    /// returns the Web API methods responses are posted with
+    pub fn methods() -> &'static [Method] {
+        RESPONDER_METHODS
+    }
+}
+
+impl Responder for ApiResponder {
+    fn respond(&self, response: Response) {
+        let result = if response.response_type == "in_channel" {
+            self.client.post_message(&self.channel, &response).map(|_| ())
+        } else {
+            self.client
+                .post_ephemeral(&self.channel, &self.user, &response)
+                .map(|_| ())
+        };
+        if let Err(err) = result {
+            error!("failed to respond to {}: {}", self.channel, err);
+        }
+    }
+}
+
+fn parse<T>(body: &str) -> Result<T>
+where
+    T: Deserialize,
+{
+    let value: Value = serde_json::from_str(body)?;
+    match value.get("ok").and_then(Value::as_bool) {
+        Some(true) => Ok(serde_json::from_value(value)?),
+        _ => {
+            let code = value
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown_error")
+                .to_owned();
+            Err(Error::from(ErrorKind::Api(code)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::server::{Request, Response as HyperResponse, Server};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Mutex};
+    use std::sync::mpsc::Receiver;
+
+    /// serves responses from `f`, sending the uri and body of each request it
+    /// receives to the returned channel so they may be asserted on the test thread
+    fn serve<F>(f: F) -> (String, ::hyper::server::Listening, Receiver<(String, String)>)
+    where
+        F: Fn(HyperResponse) + Send + Sync + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let listening = Server::http("127.0.0.1:0")
+            .unwrap()
+            .handle(move |mut req: Request, res: HyperResponse| {
+                let mut body = String::new();
+                let _ = req.read_to_string(&mut body);
+                let _ = tx.lock().unwrap().send((req.uri.to_string(), body));
+                f(res)
+            })
+            .unwrap();
+        (format!("http://{}", listening.socket), listening, rx)
+    }
+
+    #[test]
+    fn posts_messages() {
+        let (url, mut listening, requests) = serve(|res: HyperResponse| {
+            let _ = res.send(br#"{"ok":true,"channel":"C123","ts":"1.2"}"#);
+        });
+        let client = Client::with_base_url("xoxb-test", url);
+        let message = client.post_message(
+            &ChannelId::new("C123").unwrap(),
+            &Response::in_channel("hello"),
+        );
+        let _ = listening.close();
+        assert_eq!(
+            requests.try_recv().ok(),
+            Some((
+                "/chat.postMessage".to_owned(),
+                r#"{"channel":"C123","text":"hello"}"#.to_owned(),
+            ))
+        );
+        assert_eq!(
+            message.unwrap(),
+            Message {
+                channel: ChannelId::new("C123").unwrap(),
+                ts: "1.2".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn reports_api_errors() {
+        let (url, mut listening, requests) = serve(|res: HyperResponse| {
+            let _ = res.send(br#"{"ok":false,"error":"user_not_found"}"#);
+        });
+        let user = Client::with_base_url("xoxb-test", url).user_info(&UserId::new("U123").unwrap());
+        let _ = listening.close();
+        assert_eq!(
+            requests.try_recv().ok().map(|(uri, _)| uri),
+            Some("/users.info".to_owned())
+        );
+        match user {
+            Err(Error(ErrorKind::Api(code), _)) => assert_eq!(code, "user_not_found"),
+            other => assert!(false, "expected api error but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn retries_rate_limited_requests() {
+        let calls = AtomicUsize::new(0);
+        let (url, mut listening, requests) = serve(move |mut res: HyperResponse| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                *res.status_mut() = StatusCode::TooManyRequests;
+                res.headers_mut().set_raw("Retry-After", vec![b"0".to_vec()]);
+                let _ = res.send(b"");
+            } else {
+                let _ = res.send(br#"{"ok":true,"channel":{"id":"C123","name":"general"}}"#);
+            }
+        });
+        let client = Client::with_base_url("xoxb-test", url);
+        let conversation = client.conversation_info(&ChannelId::new("C123").unwrap());
+        let _ = listening.close();
+        assert_eq!(requests.try_iter().count(), 2);
+        assert_eq!(conversation.unwrap().name, Some("general".to_owned()));
+    }
+
+    #[test]
+    fn fails_when_retries_are_exhausted() {
+        let (url, mut listening, requests) = serve(|mut res: HyperResponse| {
+            *res.status_mut() = StatusCode::TooManyRequests;
+            res.headers_mut().set_raw("Retry-After", vec![b"0".to_vec()]);
+            let _ = res.send(b"");
+        });
+        let mut client = Client::with_base_url("xoxb-test", url);
+        let deleted = client
+            .retries(0)
+            .delete(&ChannelId::new("C123").unwrap(), "1.2");
+        let _ = listening.close();
+        assert_eq!(requests.try_iter().count(), 1);
+        match deleted {
+            Err(Error(ErrorKind::RateLimited(retry_after), _)) => assert_eq!(retry_after, Some(0)),
+            other => assert!(false, "expected rate limit error but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn responds_with_api_methods() {
+        let (url, mut listening, requests) = serve(|res: HyperResponse| {
+            let _ = res.send(br#"{"ok":true,"message_ts":"1.2"}"#);
+        });
+        let responder = ApiResponder::new(
+            Arc::new(Client::with_base_url("xoxb-test", url)),
+            ChannelId::new("C123").unwrap(),
+            UserId::new("U123").unwrap(),
+        );
+        responder.respond(Response::ephemeral("later"));
+        let _ = listening.close();
+        assert_eq!(
+            requests.try_recv().ok(),
+            Some((
+                "/chat.postEphemeral".to_owned(),
+                r#"{"channel":"C123","text":"later","user":"U123"}"#.to_owned(),
+            ))
+        );
+    }
+}
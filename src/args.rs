@@ -0,0 +1,454 @@
+//! Shell-style tokenizing of command text
+
+use errors::{ErrorKind, Result};
+use std::fmt::Display;
+use std::ops::Index;
+use std::slice::Iter;
+use std::str::FromStr;
+
+/// A single argument parsed from command text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arg {
+    /// the argument value with quotes and escapes removed
+    pub value: String,
+    /// true when any part of the argument was quoted. Quoted
+    /// arguments are never treated as options
+    pub quoted: bool,
+}
+
+impl Arg {
+    /// returns true if this is an unquoted option such as `--force` or `-f`
+    pub fn is_option(&self) -> bool {
+        !self.quoted && self.value.len() > 1 && self.value.starts_with('-')
+    }
+}
+
+/// A list of arguments tokenized from command text with
+/// shell-like quoting and escaping
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Args {
+    args: Vec<Arg>,
+}
+
+impl Args {
+    /// Tokenizes text, splitting on unquoted whitespace.
+    ///
+    /// Single and double quotes group words as they would in a shell and
+    /// a backslash escapes the following character outside of single quotes.
+    /// Slack's html entity escapes (`&amp;`, `&lt;`, `&gt;`) are decoded.
+    /// Curly quotes group words like their straight equivalents but are only
+    /// closed by their curly counterparts, and a right single curly quote
+    /// between letters or outside of quotes is kept as an apostrophe
+    pub fn parse(text: &str) -> Result<Args> {
+        let text = unescape(text);
+        let mut args = vec![];
+        let mut current: Option<Arg> = None;
+        // the character which opened the current quote
+        let mut quote: Option<char> = None;
+        let mut prev: Option<char> = None;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(open) if closes(open, c, prev, chars.peek().cloned()) => quote = None,
+                Some(open) if normalize(open) == '"' && c == '\\' => {
+                    match chars.next() {
+                        Some(next) if normalize(next) == '"' || next == '\\' => {
+                            push(&mut current, next)
+                        }
+                        Some(next) => {
+                            push(&mut current, '\\');
+                            push(&mut current, next)
+                        }
+                        None => push(&mut current, '\\'),
+                    }
+                }
+                Some(_) => push(&mut current, c),
+                None => {
+                    match normalize(c) {
+                        '\'' if c == '\u{2019}' => push(&mut current, c),
+                        '\'' | '"' => {
+                            quote = Some(c);
+                            current
+                                .get_or_insert(Arg {
+                                    value: String::new(),
+                                    quoted: true,
+                                })
+                                .quoted = true;
+                        }
+                        '\\' => {
+                            if let Some(next) = chars.next() {
+                                push(&mut current, next)
+                            }
+                        }
+                        _ if c.is_whitespace() => {
+                            if let Some(arg) = current.take() {
+                                args.push(arg)
+                            }
+                        }
+                        _ => push(&mut current, c),
+                    }
+                }
+            }
+            prev = Some(c);
+        }
+        if let Some(q) = quote {
+            return Err(ErrorKind::InvalidArgs(format!("unterminated {} quote", q)).into());
+        }
+        if let Some(arg) = current {
+            args.push(arg)
+        }
+        Ok(Args { args: args })
+    }
+
+    /// returns the number of arguments
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// returns true if there are no arguments
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// returns an iterator over arguments
+    pub fn iter(&self) -> Iter<Arg> {
+        self.args.iter()
+    }
+
+    /// returns the value of the argument at the provided position
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(|arg| &arg.value[..])
+    }
+
+    /// returns the values of all arguments which are not options.
+    /// All arguments following a `--` argument are considered positional
+    pub fn positional(&self) -> Vec<&str> {
+        let mut positional = vec![];
+        let mut options = true;
+        for arg in self.args.iter() {
+            if options && !arg.quoted && arg.value == "--" {
+                options = false;
+            } else if !options || !arg.is_option() {
+                positional.push(&arg.value[..])
+            }
+        }
+        positional
+    }
+
+    /// returns true if the provided option, i.e. `--force`, is present
+    pub fn flag(&self, name: &str) -> bool {
+        self.options().any(|arg| arg.value == name)
+    }
+
+    /// returns the value of the provided option, given either
+    /// as `--name=value` or `--name value`
+    pub fn value(&self, name: &str) -> Option<&str> {
+        let prefix = format!("{}=", name);
+        let mut iter = self.args.iter();
+        while let Some(arg) = iter.next() {
+            if !arg.is_option() {
+                continue;
+            }
+            if arg.value == "--" {
+                return None;
+            }
+            if arg.value.starts_with(&prefix) {
+                return Some(&arg.value[prefix.len()..]);
+            }
+            if arg.value == name {
+                return iter.next().map(|next| &next.value[..]);
+            }
+        }
+        None
+    }
+
+    fn options<'a>(&'a self) -> Box<Iterator<Item = &'a Arg> + 'a> {
+        Box::new(
+            self.args
+                .iter()
+                .take_while(|arg| arg.quoted || arg.value != "--")
+                .filter(|arg| arg.is_option()),
+        )
+    }
+}
+
+impl Index<usize> for Args {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        &self.args[index].value
+    }
+}
+
+impl<'a> IntoIterator for &'a Args {
+    type Item = &'a Arg;
+    type IntoIter = Iter<'a, Arg>;
+
+    fn into_iter(self) -> Iter<'a, Arg> {
+        self.args.iter()
+    }
+}
+
+/// Types which may be extracted from command arguments.
+/// This is typically derived with `#[derive(CommandArgs)]` from the
+/// `commodore_derive` crate
+pub trait CommandArgs: Sized {
+    /// extracts an instance from arguments, failing with
+    /// `ErrorKind::InvalidArgs` describing the problem
+    fn from_args(args: &Args) -> Result<Self>;
+
+    /// returns a description of the expected arguments,
+    /// i.e. `<service> --env <env> [--force]`
+    fn usage() -> String;
+}
+
+/// A schema aware argument reader used by derived `CommandArgs` implementations
+#[doc(hidden)]
+pub struct ArgsParser<'a> {
+    args: &'a Args,
+    positional: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> ArgsParser<'a> {
+    /// creates a parser which recognizes the provided flags, which take no
+    /// values, and options, which take values. Any other option, or a flag
+    /// given a value as in `--force=yes`, is an error
+    pub fn new(args: &'a Args, flags: &[&str], options: &[&str]) -> Result<ArgsParser<'a>> {
+        let mut positional = vec![];
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if !arg.is_option() {
+                positional.push(&arg.value[..]);
+                continue;
+            }
+            if arg.value == "--" {
+                positional.extend(iter.map(|arg| &arg.value[..]));
+                break;
+            }
+            let name = arg.value.splitn(2, '=').next().unwrap_or_default();
+            if options.contains(&name) {
+                if name == arg.value {
+                    // skip the option's value
+                    iter.next();
+                }
+            } else if !flags.contains(&name) {
+                return Err(ErrorKind::InvalidArgs(format!("unknown option `{}`", name)).into());
+            } else if name != arg.value {
+                return Err(ErrorKind::InvalidArgs(format!("`{}` does not take a value", name)).into());
+            }
+        }
+        Ok(ArgsParser {
+            args: args,
+            positional: positional,
+            next: 0,
+        })
+    }
+
+    /// reads the next required positional argument
+    pub fn positional<T>(&mut self, name: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.optional(name)? {
+            Some(value) => Ok(value),
+            _ => Err(ErrorKind::InvalidArgs(format!("missing <{}>", name)).into()),
+        }
+    }
+
+    /// reads the next positional argument, if any
+    pub fn optional<T>(&mut self, name: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.positional.get(self.next) {
+            Some(value) => {
+                self.next += 1;
+                parse(name, value).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// reads all remaining positional arguments
+    pub fn rest<T>(&mut self, name: &str) -> Result<Vec<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let mut values = vec![];
+        while let Some(value) = self.optional(name)? {
+            values.push(value)
+        }
+        Ok(values)
+    }
+
+    /// reads the value of an option which must be provided
+    pub fn required_option<T>(&self, name: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.option(name)? {
+            Some(value) => Ok(value),
+            _ => Err(ErrorKind::InvalidArgs(format!("missing `{}`", name)).into()),
+        }
+    }
+
+    /// reads the value of an option, if provided
+    pub fn option<T>(&self, name: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.args.value(name) {
+            Some(value) => parse(name, value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// returns true if the provided flag is present
+    pub fn flag(&self, name: &str) -> bool {
+        self.args.flag(name)
+    }
+
+    /// fails if any positional arguments were left unread
+    pub fn finish(self) -> Result<()> {
+        match self.positional.get(self.next) {
+            Some(value) => Err(ErrorKind::InvalidArgs(format!("unexpected argument `{}`", value)).into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn parse<T>(name: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|err: T::Err| {
+        ErrorKind::InvalidArgs(format!("invalid value `{}` for {}: {}", value, name, err)).into()
+    })
+}
+
+fn push(current: &mut Option<Arg>, c: char) {
+    current
+        .get_or_insert(Arg {
+            value: String::new(),
+            quoted: false,
+        })
+        .value
+        .push(c)
+}
+
+/// maps curly quotes to their straight equivalents
+fn normalize(c: char) -> char {
+    match c {
+        '\u{201C}' | '\u{201D}' | '\u{201E}' => '"',
+        '\u{2018}' | '\u{2019}' => '\'',
+        _ => c,
+    }
+}
+
+/// returns true if `c` closes a quote opened with `open`. Straight quotes are
+/// only closed by themselves and curly quotes by their curly counterparts. A
+/// right single curly quote between letters is an apostrophe, as in `‘don’t’`
+fn closes(open: char, c: char, prev: Option<char>, next: Option<char>) -> bool {
+    let letter = |c: Option<char>| c.map_or(false, char::is_alphanumeric);
+    match open {
+        '\'' | '"' => c == open,
+        '\u{2018}' => c == '\u{2019}' && !(letter(prev) && letter(next)),
+        _ => c == '\u{201C}' || c == '\u{201D}',
+    }
+}
+
+/// decodes the html entities Slack escapes in message text
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(text: &str) -> Vec<String> {
+        Args::parse(text)
+            .unwrap()
+            .iter()
+            .map(|arg| arg.value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(values("  deploy\tapi\u{00A0} now "), vec!["deploy", "api", "now"]);
+        assert!(Args::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn respects_quotes_and_escapes() {
+        assert_eq!(
+            values(r#"say "hello world" 'it''s' a\ b "q\"uote""#),
+            vec!["say", "hello world", "its", "a b", "q\"uote"]
+        );
+        assert_eq!(values("say “hello world” don’t"), vec!["say", "hello world", "don’t"]);
+        assert_eq!(values("‘single quoted’ \"\""), vec!["single quoted", ""]);
+    }
+
+    #[test]
+    fn matches_curly_quotes_with_their_openers() {
+        assert_eq!(values("‘don’t stop’ 'won’t'"), vec!["don’t stop", "won’t"]);
+        assert_eq!(
+            values(r#""say “hi”" “it's "fine"” „ok“"#),
+            vec!["say “hi”", "it's \"fine\"", "ok"]
+        );
+        assert!(Args::parse("“hello\"").is_err());
+        assert!(Args::parse("'hello’").is_err());
+    }
+
+    #[test]
+    fn decodes_html_entities() {
+        assert_eq!(values("a&amp;b &lt;tag&gt;"), vec!["a&b", "<tag>"]);
+    }
+
+    #[test]
+    fn fails_on_unterminated_quotes() {
+        assert!(Args::parse("say \"hello").is_err());
+    }
+
+    #[test]
+    fn extracts_options() {
+        let args = Args::parse("deploy api --env=prod --force -v \"--quoted\" -- --literal").unwrap();
+        assert_eq!(args.positional(), vec!["deploy", "api", "--quoted", "--literal"]);
+        assert!(args.flag("--force"));
+        assert!(args.flag("-v"));
+        assert!(!args.flag("--literal"));
+        assert_eq!(args.value("--env"), Some("prod"));
+        let args = Args::parse("--env staging").unwrap();
+        assert_eq!(args.value("--env"), Some("staging"));
+        assert_eq!(&args[1], "staging");
+    }
+
+    #[test]
+    fn reads_arguments_with_a_schema() {
+        let args = Args::parse("api 3 --env prod extra --force").unwrap();
+        let mut parser = ArgsParser::new(&args, &["--force"], &["--env"]).unwrap();
+        assert_eq!(parser.positional::<String>("service").unwrap(), "api");
+        assert_eq!(parser.optional::<u32>("count").unwrap(), Some(3));
+        assert_eq!(parser.required_option::<String>("--env").unwrap(), "prod");
+        assert!(parser.flag("--force"));
+        assert!(parser.finish().is_err());
+        assert!(ArgsParser::new(&args, &[], &["--env"]).is_err());
+        let args = Args::parse("api --force=no").unwrap();
+        assert!(ArgsParser::new(&args, &["--force"], &[]).is_err());
+        let args = Args::parse("api many").unwrap();
+        let mut parser = ArgsParser::new(&args, &[], &[]).unwrap();
+        assert_eq!(parser.rest::<String>("services").unwrap(), vec!["api", "many"]);
+        let mut parser = ArgsParser::new(&args, &[], &[]).unwrap();
+        assert!(parser.rest::<u32>("counts").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Renders a catalogue of the commands a Mux serves as markdown or json
+
+use super::{Mux, Route};
+use errors::Result;
+use serde_json;
+
+/// A catalogue entry describing a route
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub commands: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+}
+
+impl<'a> From<&'a Route> for Entry {
+    fn from(route: &'a Route) -> Entry {
+        let info = route.info().clone();
+        Entry {
+            name: info.name,
+            commands: route.commands(),
+            description: info.description,
+            usage: info.usage,
+            team: info.team,
+        }
+    }
+}
+
+/// Returns an entry for each of a Mux's routes in the order they were registered
+pub fn entries(mux: &Mux) -> Vec<Entry> {
+    mux.routes().map(Entry::from).collect()
+}
+
+/// escapes text for use within a markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Renders a markdown table of a Mux's routes
+pub fn markdown(mux: &Mux) -> String {
+    let mut table = vec![
+        "| Name | Commands | Usage | Description | Team |".to_owned(),
+        "| --- | --- | --- | --- | --- |".to_owned(),
+    ];
+    for entry in entries(mux) {
+        let commands = entry
+            .commands
+            .iter()
+            .map(|command| format!("`{}`", cell(command)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        table.push(format!(
+            "| {} | {} | {} | {} | {} |",
+            entry.name.as_ref().map(|name| cell(name)).unwrap_or_default(),
+            commands,
+            entry
+                .usage
+                .as_ref()
+                .map(|usage| format!("`{}`", cell(usage)))
+                .unwrap_or_default(),
+            entry
+                .description
+                .as_ref()
+                .map(|description| cell(description))
+                .unwrap_or_default(),
+            entry.team.as_ref().map(|team| cell(team)).unwrap_or_default()
+        ));
+    }
+    table.join("\n")
+}
+
+/// Renders a json array of a Mux's routes
+pub fn json(mux: &Mux) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&entries(mux))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{MatchCommand, SubCommands};
+    use testing::ok;
+
+    fn mux() -> Mux {
+        let mut mux = Mux::new();
+        let mut ops = SubCommands::new();
+        ops.command("deploy", "<service>", "deploys a service", ok);
+        mux.command("/ops", "token", ops)
+            .name("ops")
+            .description("operates services | infrastructure")
+            .usage("deploy <service>")
+            .team("platform");
+        mux.matching(MatchCommand::new("/weather"), ok);
+        mux
+    }
+
+    #[test]
+    fn renders_markdown() {
+        assert_eq!(
+            markdown(&mux()),
+            "| Name | Commands | Usage | Description | Team |\n\
+             | --- | --- | --- | --- | --- |\n\
+             | ops | `/ops deploy`, `/ops` | `deploy <service>` | \
+             operates services \\| infrastructure | platform |\n\
+             |  | `/weather` |  |  |  |"
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        assert_eq!(
+            json(&mux()).unwrap(),
+            r#"[
+  {
+    "name": "ops",
+    "commands": [
+      "/ops deploy",
+      "/ops"
+    ],
+    "description": "operates services | infrastructure",
+    "usage": "deploy <service>",
+    "team": "platform"
+  },
+  {
+    "commands": [
+      "/weather"
+    ]
+  }
+]"#
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Confirmation prompts for commands which should not run unchecked
+
+use super::{Command, Context, DefaultResponder, Flow, Handler, Middleware, Mux, Params,
+            RequestInfo};
+use response::{Action, Attachment, Response};
+use errors::Result;
+use rand::{OsRng, Rng};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// the param a confirmed command is redelivered with. Regex group names
+/// may not contain `.`, so commands can not capture it themselves
+pub const CONFIRMED: &'static str = "commodore.confirmed";
+
+/// prefixes the `callback_id` of confirmation prompts, telling them
+/// apart from the interactive messages of an app's own handlers
+const CALLBACK_PREFIX: &'static str = "commodore.confirm.";
+
+/// A command awaiting confirmation from the user who issued it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingAction {
+    /// the `callback_id` of the prompt
+    pub id: String,
+    /// the command as it was delivered
+    pub command: Command,
+    /// when the prompt stops accepting confirmation
+    pub expires: Instant,
+}
+
+/// Storage interface for commands awaiting confirmation
+pub trait PendingActions: Send + Sync {
+    /// stores an action
+    fn insert(&self, action: PendingAction);
+
+    /// removes and returns an action
+    fn take(&self, id: &str) -> Option<PendingAction>;
+}
+
+/// Stores pending actions in memory. Expired actions are removed as new ones are stored
+#[derive(Default)]
+pub struct MemoryPendingActions {
+    actions: Mutex<HashMap<String, PendingAction>>,
+}
+
+impl MemoryPendingActions {
+    pub fn new() -> MemoryPendingActions {
+        MemoryPendingActions { ..Default::default() }
+    }
+}
+
+impl PendingActions for MemoryPendingActions {
+    fn insert(&self, action: PendingAction) {
+        let mut actions = self.actions.lock().unwrap();
+        let now = Instant::now();
+        actions.retain(|_, action| action.expires > now);
+        actions.insert(action.id.clone(), action);
+    }
+
+    fn take(&self, id: &str) -> Option<PendingAction> {
+        self.actions.lock().unwrap().remove(id)
+    }
+}
+
+/// The prompt a route asks users to confirm and how long they have to do so
+pub struct Confirmation {
+    pub prompt: String,
+    pub timeout: Duration,
+}
+
+/// A middleware which stores commands as pending actions and replies
+/// with a confirmation prompt in place of running their handler
+pub struct Confirm<'a> {
+    confirmation: &'a Confirmation,
+    pending: &'a PendingActions,
+    origin: &'a Command,
+}
+
+impl<'a> Confirm<'a> {
+    pub fn new(
+        confirmation: &'a Confirmation,
+        pending: &'a PendingActions,
+        origin: &'a Command,
+    ) -> Confirm<'a> {
+        Confirm {
+            confirmation: confirmation,
+            pending: pending,
+            origin: origin,
+        }
+    }
+}
+
+impl<'a> Middleware for Confirm<'a> {
+    fn before(&self, _: &Command, params: &Params) -> Flow {
+        if params.name(CONFIRMED).is_some() {
+            return Flow::Continue;
+        }
+        let id = match issue_id() {
+            Ok(id) => id,
+            Err(err) => {
+                error!("failed to issue a confirmation id: {}", err);
+                return Flow::Respond(Some(Response::ephemeral(format!(
+                    "Unable to confirm `{}`. Try again later",
+                    invoked(self.origin)
+                ))));
+            }
+        };
+        self.pending.insert(PendingAction {
+            id: id.clone(),
+            command: self.origin.clone(),
+            expires: Instant::now() + self.confirmation.timeout,
+        });
+        let prompt = self.confirmation.prompt.replace("{}", &invoked(self.origin));
+        Flow::Respond(Some(
+            Response::builder()
+                .text(prompt.clone())
+                .attach(
+                    Attachment::builder()
+                        .fallback(prompt)
+                        .callback_id(id)
+                        .action(Action::button("confirm", "Confirm", "confirm").style("danger"))
+                        .action(Action::button("cancel", "Cancel", "cancel"))
+                        .build(),
+                )
+                .build(),
+        ))
+    }
+}
+
+/// An interactive message payload Slack sends when a button is clicked.
+/// see https://api.slack.com/docs/interactive-message-field-guide
+#[derive(Debug, Default, Deserialize)]
+pub struct ActionPayload {
+    pub callback_id: String,
+    pub token: String,
+    pub response_url: String,
+    pub user: PayloadUser,
+    #[serde(default)]
+    pub actions: Vec<PayloadAction>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PayloadUser {
+    pub id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PayloadAction {
+    pub name: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// Handler interface for interactive message payloads which
+/// do not answer a confirmation prompt
+pub trait ActionHandler: Send + Sync {
+    /// answers a payload. Implementations are responsible
+    /// for verifying the payload's `token`
+    fn handle(&self, payload: &ActionPayload, request: &RequestInfo) -> Option<Response>;
+}
+
+impl<F> ActionHandler for F
+where
+    F: Fn(&ActionPayload, &RequestInfo) -> Option<Response>,
+    F: Send + Sync,
+{
+    fn handle(&self, payload: &ActionPayload, request: &RequestInfo) -> Option<Response> {
+        self(payload, request)
+    }
+}
+
+/// Answers a click on a confirmation prompt, redelivering its command
+/// to the Mux when confirmed by the user who issued it before it expired.
+/// Payloads for other interactive messages are passed to the Mux's `ActionHandler`
+pub fn interact(mux: &Mux, payload: &ActionPayload, request: &RequestInfo) -> Option<Response> {
+    if !payload.callback_id.starts_with(CALLBACK_PREFIX) {
+        return match mux.actions {
+            Some(ref actions) => actions.handle(payload, request),
+            _ => {
+                debug!(
+                    "no action handler for callback_id {:?}",
+                    payload.callback_id
+                );
+                None
+            }
+        };
+    }
+    let action = match mux.pending.take(&payload.callback_id) {
+        Some(action) => action,
+        _ => return Some(Response::ephemeral("This confirmation is no longer pending")),
+    };
+    if payload.token != action.command.token {
+        error!(
+            "payload token ${:?} did not match cmd token ${:?}",
+            payload.token,
+            action.command.token
+        );
+        mux.pending.insert(action);
+        return None;
+    }
+    if payload.user.id != action.command.user_id.as_str() {
+        let reply = format!(
+            "Only the user who issued `{}` may confirm it",
+            invoked(&action.command)
+        );
+        mux.pending.insert(action);
+        return Some(Response::ephemeral(reply));
+    }
+    if action.expires <= Instant::now() {
+        return Some(Response::ephemeral(format!(
+            "Confirmation of `{}` timed out",
+            invoked(&action.command)
+        )));
+    }
+    let confirmed = payload.actions.first().map_or(false, |clicked| {
+        clicked.value.as_ref().unwrap_or(&clicked.name) == "confirm"
+    });
+    if !confirmed {
+        return Some(Response::ephemeral(
+            format!("Cancelled `{}`", invoked(&action.command)),
+        ));
+    }
+    debug!("redelivering confirmed cmd {:#?}", action.command);
+    let mut params = Params::new();
+    params.insert(CONFIRMED, action.id.clone());
+    let responder = DefaultResponder::new(payload.response_url.clone());
+    mux.as_handler().handle_context(Context::new(
+        &action.command,
+        &params,
+        Box::new(responder),
+        request,
+    ))
+}
+
+/// returns a command as it was typed
+fn invoked(cmd: &Command) -> String {
+    if cmd.text.is_empty() {
+        cmd.command.clone()
+    } else {
+        format!("{} {}", cmd.command, cmd.text)
+    }
+}
+
+/// issues an id for a pending action drawn from the
+/// operating system's random number generator
+fn issue_id() -> Result<String> {
+    let mut rng = OsRng::new()?;
+    Ok(format!(
+        "{}{:016x}{:016x}",
+        CALLBACK_PREFIX,
+        rng.next_u64(),
+        rng.next_u64()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{RateLimit, RateLimitKey, Responder};
+    use testing::{NoopResponder, command};
+
+    #[test]
+    fn runs_confirmed_commands_once_through_middleware() {
+        let mut mux = Mux::new();
+        mux.command(
+            "/deploy",
+            "token",
+            |cmd: &Command, _: &Params, _: Box<Responder>| {
+                Some(Response::ephemeral(format!("deployed {}", cmd.text)))
+            },
+        ).middleware(RateLimit::new(
+            RateLimitKey::User,
+            1,
+            Duration::from_secs(60),
+        ))
+            .confirm("Really run `{}`?", Duration::from_secs(60));
+        let cmd = command("/deploy").token("token").user("U1").text("api").build();
+        let prompt = mux.as_handler()
+            .handle(&cmd, &Params::new(), Box::new(NoopResponder))
+            .unwrap();
+        let payload = ActionPayload {
+            callback_id: prompt.attachments[0].callback_id.clone().unwrap(),
+            token: "token".to_owned(),
+            user: PayloadUser { id: "U1".to_owned() },
+            actions: vec![
+                PayloadAction {
+                    name: "confirm".to_owned(),
+                    value: Some("confirm".to_owned()),
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            interact(&mux, &payload, &RequestInfo::default()).and_then(|res| res.text),
+            Some("deployed api".to_owned())
+        );
+    }
+}
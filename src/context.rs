@@ -0,0 +1,233 @@
+//! Request context and shared application state provided to handlers
+
+use super::{Command, Handler, Params, PendingActions, Responder};
+use hyper::header::Headers;
+use response::Response;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static REQUEST_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Application state keyed by type. Values are shared by all
+/// requests, so wrap values handlers need to move into threads in an `Arc`
+#[derive(Default)]
+pub struct State {
+    values: HashMap<TypeId, Box<Any + Send + Sync>>,
+}
+
+impl State {
+    pub fn new() -> State {
+        State { ..Default::default() }
+    }
+
+    /// stores a value, replacing any value of the same type
+    pub fn insert<T>(&mut self, value: T)
+    where
+        T: Any + Send + Sync,
+    {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// returns the stored value of a type, if any
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.values.get(&TypeId::of::<T>()).and_then(|value| {
+            let value: &Any = &**value;
+            value.downcast_ref::<T>()
+        })
+    }
+}
+
+/// Information about the http request a command was delivered with
+#[derive(Clone)]
+pub struct RequestInfo {
+    /// an id for the request, taken from the `X-Request-Id` header when provided
+    pub id: String,
+    pub remote_addr: Option<SocketAddr>,
+    pub headers: Headers,
+}
+
+impl RequestInfo {
+    /// creates request info for the provided headers
+    pub fn new(remote_addr: Option<SocketAddr>, headers: Headers) -> RequestInfo {
+        let id = headers
+            .get_raw("X-Request-Id")
+            .and_then(|values| values.first())
+            .and_then(|value| str::from_utf8(value).ok())
+            .map(|value| value.to_owned())
+            .unwrap_or_else(generate_id);
+        RequestInfo {
+            id: id,
+            remote_addr: remote_addr,
+            headers: headers,
+        }
+    }
+
+    /// returns the first value of a header as a string, if present
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get_raw(name)
+            .and_then(|values| values.first())
+            .and_then(|value| str::from_utf8(value).ok())
+    }
+}
+
+impl Default for RequestInfo {
+    fn default() -> RequestInfo {
+        RequestInfo::new(None, Headers::new())
+    }
+}
+
+/// generates a request id from the time and a process wide counter
+fn generate_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() * 1000 + u64::from(now.subsec_nanos() / 1_000_000))
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}",
+        millis,
+        REQUEST_COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// A command along with the params captured while matching it, an interface
+/// for deferred responses, the request it was delivered with, and application state
+pub struct Context<'a> {
+    pub command: &'a Command,
+    pub params: &'a Params,
+    pub responder: Box<Responder>,
+    pub request: &'a RequestInfo,
+    origin: &'a Command,
+    states: Vec<&'a State>,
+    pending: Option<&'a PendingActions>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(
+        command: &'a Command,
+        params: &'a Params,
+        responder: Box<Responder>,
+        request: &'a RequestInfo,
+    ) -> Context<'a> {
+        Context {
+            command: command,
+            params: params,
+            responder: responder,
+            request: request,
+            origin: command,
+            states: vec![],
+            pending: None,
+        }
+    }
+
+    /// makes state available to handlers. State added later takes
+    /// precedence over earlier state holding a value of the same type
+    pub fn with_state(mut self, state: &'a State) -> Context<'a> {
+        self.states.insert(0, state);
+        self
+    }
+
+    /// returns a copy of this context for a different command and params
+    pub fn with_command<'b>(self, command: &'b Command, params: &'b Params) -> Context<'b>
+    where
+        'a: 'b,
+    {
+        Context {
+            command: command,
+            params: params,
+            ..self
+        }
+    }
+
+    /// makes a store for actions awaiting confirmation available,
+    /// unless one already is
+    pub fn with_pending_actions(mut self, pending: &'a PendingActions) -> Context<'a> {
+        if self.pending.is_none() {
+            self.pending = Some(pending);
+        }
+        self
+    }
+
+    /// returns the store for actions awaiting confirmation, if any
+    pub fn pending_actions(&self) -> Option<&'a PendingActions> {
+        self.pending
+    }
+
+    /// returns the command as it was delivered, before any
+    /// mounted prefix or subcommand was removed from its text
+    pub fn origin(&self) -> &'a Command {
+        self.origin
+    }
+
+    /// returns application state of a type registered with `Mux::state`, if any
+    pub fn state<T>(&self) -> Option<&'a T>
+    where
+        T: Any + Send + Sync,
+    {
+        self.states.iter().filter_map(|state| state.get::<T>()).next()
+    }
+}
+
+/// A Handler for functions which accept a `Context`
+pub struct WithContext<F>(F);
+
+/// Returns a Handler for a function which accepts a `Context`. Handled
+/// without a Mux, the context has default request info and no state
+pub fn with_context<F>(handler: F) -> WithContext<F>
+where
+    F: Fn(Context) -> Option<Response> + Send + Sync,
+{
+    WithContext(handler)
+}
+
+impl<F> Handler for WithContext<F>
+where
+    F: Fn(Context) -> Option<Response> + Send + Sync,
+{
+    fn handle(&self, cmd: &Command, params: &Params, responder: Box<Responder>) -> Option<Response> {
+        let request = RequestInfo::default();
+        (self.0)(Context::new(cmd, params, responder, &request))
+    }
+
+    fn handle_context(&self, ctx: Context) -> Option<Response> {
+        (self.0)(ctx)
+    }
+
+    fn as_handler(&self) -> &Handler {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::Headers;
+
+    #[test]
+    fn stores_state_by_type() {
+        let mut state = State::new();
+        state.insert(42u32);
+        state.insert("config".to_owned());
+        state.insert(7u32);
+        assert_eq!(state.get::<u32>(), Some(&7));
+        assert_eq!(state.get::<String>().map(|s| &s[..]), Some("config"));
+        assert_eq!(state.get::<i64>(), None);
+    }
+
+    #[test]
+    fn identifies_requests() {
+        let mut headers = Headers::new();
+        headers.set_raw("X-Request-Id", vec![b"abc123".to_vec()]);
+        let request = RequestInfo::new(None, headers);
+        assert_eq!(request.id, "abc123");
+        assert_eq!(request.header("x-request-id"), Some("abc123"));
+        assert!(RequestInfo::default().id != RequestInfo::default().id);
+    }
+}
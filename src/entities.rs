@@ -0,0 +1,311 @@
+//! Parsing of Slack's escaped entity references in command text
+//!
+//! When "Escape channels, users, and links" is enabled for a command, mentions
+//! and links are sent as references like `<@U123|alice>`, `<#C456|general>`,
+//! `<!subteam^S789>`, `<!here>` and `<https://example.com|label>`.
+//! See [formatting](https://api.slack.com/reference/surfaces/formatting#retrieving-messages)
+
+use super::{Captures, Command, Matcher};
+use ids::{ChannelId, UserGroupId, UserId};
+use regex::Regex;
+
+/// A reference to a user, i.e. `<@U123|alice>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserRef {
+    pub id: UserId,
+    pub name: Option<String>,
+}
+
+/// A reference to a channel, i.e. `<#C456|general>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelRef {
+    pub id: ChannelId,
+    pub name: Option<String>,
+}
+
+/// A reference to a user group, i.e. `<!subteam^S789|@oncall>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserGroupRef {
+    pub id: UserGroupId,
+    pub handle: Option<String>,
+}
+
+/// A link, i.e. `<https://example.com|label>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub url: String,
+    pub label: Option<String>,
+}
+
+/// Special mentions which notify groups of people
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecialMention {
+    Here,
+    Channel,
+    Everyone,
+}
+
+/// An entity referenced within command text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entity {
+    User(UserRef),
+    Channel(ChannelRef),
+    UserGroup(UserGroupRef),
+    Link(Link),
+    Special(SpecialMention),
+}
+
+/// An entity along with the byte range of its reference within the text it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityMatch {
+    pub entity: Entity,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Regex pattern for a user reference, capturing `user_id` and `user_name`
+pub const USER_PATTERN: &'static str = r"<@(?P<user_id>[UW][A-Z0-9]+)(?:\|(?P<user_name>[^>]*))?>";
+/// Regex pattern for a channel reference, capturing `channel_id` and `channel_name`
+pub const CHANNEL_PATTERN: &'static str = r"<#(?P<channel_id>[CGD][A-Z0-9]+)(?:\|(?P<channel_name>[^>]*))?>";
+/// Regex pattern for a user group reference, capturing `usergroup_id` and `usergroup_handle`
+pub const USERGROUP_PATTERN: &'static str = r"<!subteam\^(?P<usergroup_id>S[A-Z0-9]+)(?:\|(?P<usergroup_handle>[^>]*))?>";
+/// Regex pattern for a link, capturing `link_url` and `link_label`
+pub const LINK_PATTERN: &'static str = r"<(?P<link_url>[a-zA-Z][a-zA-Z0-9+.-]*:[^|>]+)(?:\|(?P<link_label>[^>]*))?>";
+
+/// Returns a pattern matching a user reference which captures the user id
+/// as `name` and the user name as `name_name`. Useful for composing `MatchText` regexes
+/// which capture multiple references
+pub fn user(name: &str) -> String {
+    format!(r"<@(?P<{0}>[UW][A-Z0-9]+)(?:\|(?P<{0}_name>[^>]*))?>", name)
+}
+
+/// Returns a pattern matching a channel reference which captures the
+/// channel id as `name` and the channel name as `name_name`
+pub fn channel(name: &str) -> String {
+    format!(r"<#(?P<{0}>[CGD][A-Z0-9]+)(?:\|(?P<{0}_name>[^>]*))?>", name)
+}
+
+/// Returns a pattern matching a user group reference which captures the
+/// group id as `name` and the group handle as `name_handle`
+pub fn usergroup(name: &str) -> String {
+    format!(r"<!subteam\^(?P<{0}>S[A-Z0-9]+)(?:\|(?P<{0}_handle>[^>]*))?>", name)
+}
+
+/// Returns a pattern matching a link which captures the url as `name`
+/// and its label as `name_label`
+pub fn link(name: &str) -> String {
+    format!(r"<(?P<{0}>[a-zA-Z][a-zA-Z0-9+.-]*:[^|>]+)(?:\|(?P<{0}_label>[^>]*))?>", name)
+}
+
+/// splits a reference into its value and optional label
+fn labeled(reference: &str) -> (String, Option<String>) {
+    let mut parts = reference.splitn(2, '|');
+    let value = parts.next().unwrap_or_default().to_owned();
+    let label = parts.next().and_then(|label| if label.is_empty() {
+        None
+    } else {
+        Some(label.to_owned())
+    });
+    (value, label)
+}
+
+/// classifies a reference, ignoring references with invalid ids
+fn entity(reference: &str) -> Option<Entity> {
+    if reference.starts_with('@') {
+        let (id, name) = labeled(&reference[1..]);
+        UserId::new(id).ok().map(|id| {
+            Entity::User(UserRef { id: id, name: name })
+        })
+    } else if reference.starts_with('#') {
+        let (id, name) = labeled(&reference[1..]);
+        ChannelId::new(id).ok().map(|id| {
+            Entity::Channel(ChannelRef { id: id, name: name })
+        })
+    } else if reference.starts_with("!subteam^") {
+        let (id, handle) = labeled(&reference["!subteam^".len()..]);
+        UserGroupId::new(id).ok().map(|id| {
+            Entity::UserGroup(UserGroupRef {
+                id: id,
+                handle: handle,
+            })
+        })
+    } else if reference.starts_with('!') {
+        match &labeled(&reference[1..]).0[..] {
+            "here" => Some(Entity::Special(SpecialMention::Here)),
+            "channel" => Some(Entity::Special(SpecialMention::Channel)),
+            "everyone" => Some(Entity::Special(SpecialMention::Everyone)),
+            // dates and other special commands are not entities
+            _ => None,
+        }
+    } else if reference.contains(':') {
+        let (url, label) = labeled(reference);
+        Some(Entity::Link(Link {
+            url: url,
+            label: label,
+        }))
+    } else {
+        None
+    }
+}
+
+/// Parses all entity references within text in the order they appear
+pub fn parse(text: &str) -> Vec<EntityMatch> {
+    let mut entities = vec![];
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('<').map(|idx| idx + offset) {
+        match text[start..].find('>').map(|idx| idx + start) {
+            Some(end) => {
+                let reference = &text[start + 1..end];
+                // a nested `<` means the first was not the start of a reference
+                if let Some(nested) = reference.rfind('<') {
+                    offset = start + 1 + nested;
+                    continue;
+                }
+                if let Some(entity) = self::entity(reference) {
+                    entities.push(EntityMatch {
+                        entity: entity,
+                        start: start,
+                        end: end + 1,
+                    })
+                }
+                offset = end + 1;
+            }
+            _ => break,
+        }
+    }
+    entities
+}
+
+impl Command {
+    /// Returns the entities referenced in this command's text
+    pub fn entities(&self) -> Vec<EntityMatch> {
+        parse(&self.text)
+    }
+
+    /// Returns the users referenced in this command's text
+    pub fn users(&self) -> Vec<UserRef> {
+        self.entities()
+            .into_iter()
+            .filter_map(|m| match m.entity {
+                Entity::User(user) => Some(user),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the channels referenced in this command's text
+    pub fn channels(&self) -> Vec<ChannelRef> {
+        self.entities()
+            .into_iter()
+            .filter_map(|m| match m.entity {
+                Entity::Channel(channel) => Some(channel),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A matcher for commands whose text references an entity of a given kind.
+/// The first reference found is captured using the group names of the
+/// corresponding `*_PATTERN`, i.e. `user_id` and `user_name` for users
+pub struct MatchEntity(Regex);
+
+impl MatchEntity {
+    /// matches text referencing a user
+    pub fn user() -> MatchEntity {
+        MatchEntity(Regex::new(USER_PATTERN).unwrap())
+    }
+
+    /// matches text referencing a channel
+    pub fn channel() -> MatchEntity {
+        MatchEntity(Regex::new(CHANNEL_PATTERN).unwrap())
+    }
+
+    /// matches text referencing a user group
+    pub fn usergroup() -> MatchEntity {
+        MatchEntity(Regex::new(USERGROUP_PATTERN).unwrap())
+    }
+
+    /// matches text including a link
+    pub fn link() -> MatchEntity {
+        MatchEntity(Regex::new(LINK_PATTERN).unwrap())
+    }
+}
+
+impl Matcher for MatchEntity {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        match Captures::from_regex(&self.0, &cmd.text) {
+            Some(caps) => (Some(caps), true),
+            _ => {
+                debug!("no entity matching {:?} in cmd {:?}", self.0, cmd.command);
+                (None, false)
+            }
+        }
+    }
+
+    fn specificity(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entities() {
+        let text = "ask <@U123|alice> and <@W9> <@nobody> in <#C456|general> with <!subteam^S789|@ops> \
+                    <!here> <!date^1392734382^{date}|Feb 18> about <https://x.com/a?b=c|label> \
+                    and a < b";
+        let entities = parse(text);
+        assert_eq!(
+            entities.iter().map(|m| m.entity.clone()).collect::<Vec<_>>(),
+            vec![
+                Entity::User(UserRef {
+                    id: UserId::new("U123").unwrap(),
+                    name: Some("alice".to_owned()),
+                }),
+                Entity::User(UserRef {
+                    id: UserId::new("W9").unwrap(),
+                    name: None,
+                }),
+                Entity::Channel(ChannelRef {
+                    id: ChannelId::new("C456").unwrap(),
+                    name: Some("general".to_owned()),
+                }),
+                Entity::UserGroup(UserGroupRef {
+                    id: UserGroupId::new("S789").unwrap(),
+                    handle: Some("@ops".to_owned()),
+                }),
+                Entity::Special(SpecialMention::Here),
+                Entity::Link(Link {
+                    url: "https://x.com/a?b=c".to_owned(),
+                    label: Some("label".to_owned()),
+                }),
+            ]
+        );
+        assert_eq!(&text[entities[0].start..entities[0].end], "<@U123|alice>");
+        assert_eq!(&text[entities[5].start..entities[5].end], "<https://x.com/a?b=c|label>");
+    }
+
+    #[test]
+    fn matches_entities() {
+        let cmd = Command {
+            text: "invite <@U123|alice> to <#C456>".to_owned(),
+            ..Default::default()
+        };
+        match MatchEntity::user().matches(&cmd) {
+            (Some(caps), true) => {
+                assert_eq!(caps.name("user_id").map(|m| m.as_str()), Some("U123"));
+                assert_eq!(caps.name("user_name").map(|m| m.as_str()), Some("alice"));
+            }
+            _ => assert!(false, "expected user captures"),
+        }
+        assert!(!MatchEntity::link().matches(&cmd).1);
+        let pattern = Regex::new(&format!("^invite {} to {}$", user("who"), channel("where"))).unwrap();
+        let caps = pattern.captures(&cmd.text).unwrap();
+        assert_eq!(caps.name("who").map(|m| m.as_str()), Some("U123"));
+        assert_eq!(caps.name("where").map(|m| m.as_str()), Some("C456"));
+        assert_eq!(caps.name("where_name"), None);
+    }
+}
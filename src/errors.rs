@@ -0,0 +1,75 @@
+//! Error types
+
+use hyper;
+use serde_json;
+use std::io;
+
+error_chain! {
+    foreign_links {
+        Http(hyper::Error);
+        Json(serde_json::Error);
+        Io(io::Error);
+    }
+
+    errors {
+        /// Slack responded with `ok: false` and the provided error code
+        Api(code: String) {
+            description("slack api error")
+            display("slack api error: {}", code)
+        }
+        /// Slack rate limited the request. Includes the suggested number
+        /// of seconds to wait before retrying, when provided
+        RateLimited(retry_after: Option<u64>) {
+            description("rate limited")
+            display("rate limited. retry after {:?} seconds", retry_after)
+        }
+        /// A command request was missing required fields or
+        /// included fields with invalid values
+        InvalidCommand(missing: Vec<String>, invalid: Vec<String>) {
+            description("invalid command")
+            display("invalid command. {}", describe(missing, invalid))
+        }
+        /// Command text could not be parsed into arguments
+        InvalidArgs(reason: String) {
+            description("invalid arguments")
+            display("invalid arguments: {}", reason)
+        }
+        /// A handler requested a param which was not captured
+        MissingParam(name: String) {
+            description("missing param")
+            display("missing param {}", name)
+        }
+        /// A captured param could not be parsed into the requested type
+        InvalidParam(name: String, value: String, reason: String) {
+            description("invalid param")
+            display("invalid value `{}` for param {}: {}", value, name, reason)
+        }
+        /// An id did not have the expected format for its kind
+        InvalidId(kind: String, id: String) {
+            description("invalid id")
+            display("invalid {} id: {:?}", kind, id)
+        }
+        /// A route can never be reached because a route which
+        /// matches every command is always preferred to it
+        UnreachableRoute(route: Vec<String>, preferred: Vec<String>) {
+            description("unreachable route")
+            display("route {:?} can never be reached because route {:?} matches every command", route, preferred)
+        }
+        /// An oauth installation could not be completed
+        OAuth(reason: String) {
+            description("oauth installation failed")
+            display("oauth installation failed: {}", reason)
+        }
+    }
+}
+
+fn describe(missing: &[String], invalid: &[String]) -> String {
+    let mut reasons = vec![];
+    if !missing.is_empty() {
+        reasons.push(format!("missing fields: {}", missing.join(", ")));
+    }
+    if !invalid.is_empty() {
+        reasons.push(format!("invalid fields: {}", invalid.join(", ")));
+    }
+    reasons.join("; ")
+}
@@ -0,0 +1,143 @@
+//! Suggestions for commands which no route matches
+
+use super::{Command, Handler, Params, Responder};
+use response::Response;
+
+/// returns the number of single character insertions, deletions,
+/// and substitutions needed to turn one string into another
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..b.len() + 1).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the name closest to what a command invoked. Names are either a
+/// command, i.e. `/ops`, or a command followed by subcommand words, i.e. `/ops deploy`,
+/// and are compared to the command followed by the same number of words of its text.
+/// Names of subcommand words alone, i.e. `deploy`, are compared to the same number of
+/// words of its text. Names which differ by more than a third of their length are not suggested
+pub fn suggest<'a, I>(cmd: &Command, names: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let words = cmd.text.split_whitespace().collect::<Vec<_>>();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let depth = match name.split_whitespace().count() {
+                0 => return None,
+                count => count - 1,
+            };
+            let invoked = if !name.starts_with('/') {
+                if depth >= words.len() {
+                    return None;
+                }
+                words[..depth + 1].join(" ")
+            } else if depth > words.len() {
+                return None;
+            } else if depth == 0 {
+                cmd.command.clone()
+            } else {
+                format!("{} {}", cmd.command, words[..depth].join(" "))
+            };
+            let distance = distance(&invoked, name);
+            if distance > 0 && distance <= (name.chars().count() / 3).max(1) {
+                Some((distance, name))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, name)| name.clone())
+}
+
+/// A fallback Handler which replies to unmatched commands with an
+/// ephemeral message suggesting the closest registered command, when
+/// `Mux` provides one as the `suggestion` param. Suggestions are drawn from
+/// `Matcher::names` and `Handler::subcommands`, so routes which report
+/// neither are never suggested
+pub struct DidYouMean;
+
+impl Handler for DidYouMean {
+    fn handle(&self, cmd: &Command, params: &Params, _: Box<Responder>) -> Option<Response> {
+        let invoked = if cmd.text.is_empty() {
+            cmd.command.clone()
+        } else {
+            format!("{} {}", cmd.command, cmd.text)
+        };
+        Some(Response::ephemeral(match params.name("suggestion") {
+            Some(suggestion) => {
+                format!(
+                    "Sorry, I don't understand `{}`. Did you mean `{}`?",
+                    invoked,
+                    suggestion
+                )
+            }
+            _ => format!("Sorry, I don't understand `{}`", invoked),
+        }))
+    }
+
+    fn as_handler(&self) -> &Handler {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::command;
+
+    #[test]
+    fn measures_distance() {
+        assert_eq!(distance("deploy", "deploy"), 0);
+        assert_eq!(distance("deplyo", "deploy"), 2);
+        assert_eq!(distance("/op", "/ops"), 1);
+        assert_eq!(distance("", "ops"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn suggests_closest_names() {
+        let names = vec![
+            "/ops".to_owned(),
+            "/ops deploy".to_owned(),
+            "/ops rollback".to_owned(),
+            "/weather".to_owned(),
+        ];
+        let cmd = |name: &str, text: &str| command(name).text(text).build();
+        assert_eq!(
+            suggest(&cmd("/ops", "deplyo api"), &names),
+            Some("/ops deploy".to_owned())
+        );
+        assert_eq!(
+            suggest(&cmd("/wether", ""), &names),
+            Some("/weather".to_owned())
+        );
+        assert_eq!(suggest(&cmd("/ops", "status"), &names), None);
+        assert_eq!(suggest(&cmd("/deploy", ""), &names), None);
+        let relative = vec!["deploy".to_owned(), "rollback now".to_owned()];
+        assert_eq!(
+            suggest(&cmd("/ops", "deplo api"), &relative),
+            Some("deploy".to_owned())
+        );
+        assert_eq!(
+            suggest(&cmd("/ops", "rollbak now"), &relative),
+            Some("rollback now".to_owned())
+        );
+        let blank = vec!["".to_owned(), " ".to_owned(), "deploy".to_owned()];
+        assert_eq!(
+            suggest(&cmd("/ops", "deplo"), &blank),
+            Some("deploy".to_owned())
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Typed identifiers for Slack teams, enterprises, channels, users, and user groups
+
+use errors::{Error, ErrorKind, Result};
+use serde::de::{self, Deserialize, Deserializer};
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+
+macro_rules! id {
+    ($(#[$attr:meta])* $name:ident, $kind:expr, $prefixes:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+        pub struct $name(String);
+
+        impl $name {
+            /// Creates a new id, validating that it has the expected prefix
+            /// and consists of only upper case letters and digits
+            pub fn new<I>(id: I) -> Result<$name>
+            where
+                I: Into<String>,
+            {
+                let id = id.into();
+                if valid(&id, $prefixes) {
+                    Ok($name(id))
+                } else {
+                    Err(ErrorKind::InvalidId($kind.to_owned(), id).into())
+                }
+            }
+
+            /// returns the id as a string slice
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<$name> {
+                $name::new(s)
+            }
+        }
+
+        /// Ids are validated as they are deserialized
+        impl Deserialize for $name {
+            fn deserialize<D>(deserializer: D) -> result::Result<$name, D::Error>
+            where
+                D: Deserializer,
+            {
+                let id = String::deserialize(deserializer)?;
+                $name::new(id).map_err(|err| de::Error::custom(err.to_string()))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl<'a> PartialEq<&'a str> for $name {
+            fn eq(&self, other: &&'a str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> String {
+                id.0
+            }
+        }
+    };
+}
+
+fn valid(id: &str, prefixes: &[char]) -> bool {
+    id.len() > 1 && id.starts_with(prefixes) &&
+        id.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+id!(
+    /// A workspace id, i.e. `T0123ABCD`
+    TeamId,
+    "team",
+    &['T']
+);
+
+id!(
+    /// An enterprise grid organization id, i.e. `E0123ABCD`
+    EnterpriseId,
+    "enterprise",
+    &['E']
+);
+
+id!(
+    /// A user id, i.e. `U0123ABCD` or `W0123ABCD` for enterprise grid users
+    UserId,
+    "user",
+    &['U', 'W']
+);
+
+id!(
+    /// A conversation id, i.e. `C0123ABCD`
+    ChannelId,
+    "channel",
+    &['C', 'G', 'D']
+);
+
+id!(
+    /// A user group id, i.e. `S0123ABCD`
+    UserGroupId,
+    "usergroup",
+    &['S']
+);
+
+/// A team or an enterprise grid organization. Settings for an
+/// enterprise cover every team within it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Workspace {
+    Team(TeamId),
+    Enterprise(EnterpriseId),
+}
+
+impl Workspace {
+    /// returns true if the provided team, or the enterprise it
+    /// belongs to, is this workspace
+    pub fn contains(&self, team_id: &TeamId, enterprise_id: Option<&EnterpriseId>) -> bool {
+        match *self {
+            Workspace::Team(ref id) => id == team_id,
+            Workspace::Enterprise(ref id) => enterprise_id == Some(id),
+        }
+    }
+}
+
+impl From<TeamId> for Workspace {
+    fn from(id: TeamId) -> Workspace {
+        Workspace::Team(id)
+    }
+}
+
+impl From<EnterpriseId> for Workspace {
+    fn from(id: EnterpriseId) -> Workspace {
+        Workspace::Enterprise(id)
+    }
+}
+
+/// Parses a team id or, for ids starting with `E`, an enterprise id
+impl FromStr for Workspace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Workspace> {
+        if s.starts_with('E') {
+            EnterpriseId::new(s).map(Workspace::Enterprise)
+        } else {
+            TeamId::new(s).map(Workspace::Team)
+        }
+    }
+}
+
+impl Deserialize for Workspace {
+    fn deserialize<D>(deserializer: D) -> result::Result<Workspace, D::Error>
+    where
+        D: Deserializer,
+    {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(|err: Error| de::Error::custom(err.to_string()))
+    }
+}
+
+impl fmt::Display for Workspace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Workspace::Team(ref id) => id.fmt(f),
+            Workspace::Enterprise(ref id) => id.fmt(f),
+        }
+    }
+}
+
+/// The kind of conversation a channel id refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    /// a public channel
+    Public,
+    /// a private channel or multi-person direct message
+    Private,
+    /// a direct message
+    Direct,
+}
+
+impl ChannelId {
+    /// returns the kind of conversation this id refers to, based on its prefix
+    pub fn kind(&self) -> ChannelKind {
+        match self.0.chars().next() {
+            Some('G') => ChannelKind::Private,
+            Some('D') => ChannelKind::Direct,
+            _ => ChannelKind::Public,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn validates_ids() {
+        assert!(UserId::new("U123ABC").is_ok());
+        assert!(UserId::new("W123ABC").is_ok());
+        assert!(UserId::new("C123ABC").is_err());
+        assert!(UserId::new("U").is_err());
+        assert!(UserId::new("u123abc").is_err());
+        assert!(TeamId::new("T123").is_ok());
+        assert!(TeamId::new("test_team").is_err());
+        assert!(EnterpriseId::new("E123").is_ok());
+        assert_eq!("C123".parse::<ChannelId>().unwrap(), "C123");
+        assert!(UserGroupId::new("S123").is_ok());
+        assert!(UserGroupId::new("U123").is_err());
+    }
+
+    #[test]
+    fn parses_workspaces() {
+        let team = "T1".parse::<Workspace>().unwrap();
+        let enterprise = "E1".parse::<Workspace>().unwrap();
+        let t1 = TeamId::new("T1").unwrap();
+        let t2 = TeamId::new("T2").unwrap();
+        let e1 = EnterpriseId::new("E1").unwrap();
+        assert_eq!(team, Workspace::Team(t1.clone()));
+        assert!(team.contains(&t1, Some(&e1)));
+        assert!(!team.contains(&t2, Some(&e1)));
+        assert!(enterprise.contains(&t2, Some(&e1)));
+        assert!(!enterprise.contains(&t2, None));
+        assert!("test_team".parse::<Workspace>().is_err());
+    }
+
+    #[test]
+    fn validates_deserialized_ids() {
+        assert_eq!(
+            serde_json::from_str::<UserId>(r#""U123ABC""#).unwrap(),
+            "U123ABC"
+        );
+        assert!(serde_json::from_str::<UserId>(r#""C123ABC""#).is_err());
+        assert!(serde_json::from_str::<TeamId>(r#""""#).is_err());
+        assert!(serde_json::from_str::<Option<TeamId>>("null").unwrap().is_none());
+    }
+
+    #[test]
+    fn detects_channel_kinds() {
+        assert_eq!(ChannelId::new("C123").unwrap().kind(), ChannelKind::Public);
+        assert_eq!(ChannelId::new("G123").unwrap().kind(), ChannelKind::Private);
+        assert_eq!(ChannelId::new("D123").unwrap().kind(), ChannelKind::Direct);
+    }
+}
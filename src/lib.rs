@@ -1,11 +1,12 @@
 //! Call rank and take command of [Slack](https://slack.com/) with rust at your helm
 
-//#[macro_use]
-//extern crate error_chain;
+#[macro_use]
+extern crate error_chain;
 #[macro_use]
 extern crate log;
 extern crate hyper;
 extern crate hyper_native_tls;
+extern crate rand;
 extern crate url;
 extern crate regex;
 extern crate serde;
@@ -17,13 +18,53 @@ use hyper::Client;
 use hyper::header::ContentType;
 use hyper::net::HttpsConnector;
 use hyper::server::{Handler as HyperHandler, Request, Response as HyperResponse};
+use hyper::status::StatusCode;
 use hyper_native_tls::NativeTlsClient;
-use regex::{Captures as RegexCaptures, Regex};
+use regex::{Captures as RegexCaptures, Match, Regex, RegexBuilder};
+use std::any::Any;
 use std::collections::HashMap;
 use std::io::Read;
+use std::result;
+use std::str::FromStr;
+use std::sync::{Arc, Once};
+use std::time::Duration;
 
 mod response;
-pub use response::{Attachment, Field, Response, ResponseBuilder, AttachmentBuilder};
+pub use response::{Action, Attachment, Field, Response, ResponseBuilder, AttachmentBuilder};
+pub mod api;
+mod args;
+pub use args::{Arg, Args, ArgsParser, CommandArgs};
+mod params;
+pub use params::Params;
+pub mod catalogue;
+mod confirm;
+pub use confirm::{ActionHandler, ActionPayload, MemoryPendingActions, PayloadAction, PayloadUser,
+                  PendingAction, PendingActions};
+use confirm::{Confirm, Confirmation};
+mod context;
+pub use context::{with_context, Context, RequestInfo, State, WithContext};
+mod fallback;
+pub use fallback::DidYouMean;
+mod middleware;
+pub use middleware::{Flow, Middleware, TeamTokenValidator, TokenValidator};
+mod ratelimit;
+pub use ratelimit::{RateLimit, RateLimitKey};
+mod mount;
+use mount::Mount;
+pub mod manifest;
+mod subcommand;
+pub use subcommand::{SubCommand, SubCommands};
+pub mod entities;
+pub use entities::MatchEntity;
+pub mod errors;
+use errors::{ErrorKind, Result};
+mod ids;
+pub use ids::{ChannelId, ChannelKind, EnterpriseId, TeamId, UserGroupId, UserId, Workspace};
+pub mod oauth;
+pub mod rbac;
+use rbac::{RequireRoles, RoleProvider};
+pub mod matcher;
+pub use matcher::{MatchChannel, MatchChannelKind, MatchTeam, MatchUser, MatcherExt};
 
 const DEFAULT_RESPONSE: &'static [u8] = b"ok";
 
@@ -37,8 +78,65 @@ fn params<R: Read>(read: &mut R) -> HashMap<String, String> {
     params
 }
 
-/// Results for regex matchers that collect captures
-pub type Captures<'a> = RegexCaptures<'a>;
+/// Results for regex matchers that collect captures. Captures
+/// collected by multiple matchers may be merged together
+#[derive(Debug)]
+pub struct Captures<'a> {
+    sets: Vec<RegexCaptures<'a>>,
+    names: Vec<Vec<String>>,
+}
+
+impl<'a> Captures<'a> {
+    /// returns the captures of a regex within text, if the regex matches
+    pub fn from_regex(regex: &Regex, text: &'a str) -> Option<Captures<'a>> {
+        regex.captures(text).map(|caps| {
+            Captures {
+                sets: vec![caps],
+                names: vec![
+                    regex
+                        .capture_names()
+                        .filter_map(|name| name.map(|name| name.to_owned()))
+                        .collect(),
+                ],
+            }
+        })
+    }
+
+    /// returns the match for a named group from the first
+    /// set of captures in which that group participated
+    pub fn name(&self, name: &str) -> Option<Match<'a>> {
+        self.sets.iter().filter_map(|caps| caps.name(name)).next()
+    }
+
+    /// returns the match for a positional group from the first set of captures
+    pub fn get(&self, index: usize) -> Option<Match<'a>> {
+        self.sets.first().and_then(|caps| caps.get(index))
+    }
+
+    /// returns the underlying sets of regex captures in the order they were collected
+    pub fn sets(&self) -> &[RegexCaptures<'a>] {
+        &self.sets
+    }
+
+    /// returns the names of all named groups across sets of captures
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for name in self.names.iter().flat_map(|names| names.iter()) {
+            if !names.contains(&&name[..]) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// combines these captures with another, giving
+    /// precedence to these captures for named lookups
+    pub fn merge(mut self, other: Captures<'a>) -> Captures<'a> {
+        self.sets.extend(other.sets);
+        self.names.extend(other.names);
+        self
+    }
+}
 
 /// Deferred response interface
 pub trait Responder: Sync + Send {
@@ -76,13 +174,13 @@ impl Responder for DefaultResponder {
 /// Command handling interface
 /// Implementation for Fn
 pub trait Handler: Sync + Send {
-    /// handles Slack commands. Optional captures resulting
-    /// from matching are provided along with an interface
+    /// handles Slack commands. Params captured while
+    /// matching are provided along with an interface
     /// for deferred responses
     fn handle(
         &self,
         cmd: &Command,
-        caps: &Option<Captures>,
+        params: &Params,
         responder: Box<Responder>,
     ) -> Option<Response>;
 
@@ -91,20 +189,32 @@ pub trait Handler: Sync + Send {
     /// already defined in another trait for which another
     /// impl exists for the same type
     fn as_handler(&self) -> &Handler;
+
+    /// handles Slack commands along with the request they were delivered
+    /// with and application state. By default this delegates to `handle`
+    fn handle_context(&self, ctx: Context) -> Option<Response> {
+        self.handle(ctx.command, ctx.params, ctx.responder)
+    }
+
+    /// returns the subcommand words this handler dispatches on, i.e. `deploy` for
+    /// `/ops deploy`. Handlers which do not dispatch on command text return none
+    fn subcommands(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 impl<F> Handler for F
 where
-    F: Fn(&Command, &Option<Captures>, Box<Responder>) -> Option<Response>,
+    F: Fn(&Command, &Params, Box<Responder>) -> Option<Response>,
     F: Send + Sync,
 {
     fn handle(
         &self,
         cmd: &Command,
-        caps: &Option<Captures>,
+        params: &Params,
         responder: Box<Responder>,
     ) -> Option<Response> {
-        self(cmd, caps, responder)
+        self(cmd, params, responder)
     }
 
     fn as_handler(&self) -> &Handler {
@@ -112,33 +222,33 @@ where
     }
 }
 
-#[doc(hidden)]
-pub struct TokenValidator<H: Handler + 'static> {
-    handler: H,
-    token: String,
+/// Per-team verification token lookup interface
+pub trait TokenLookup: Send + Sync {
+    /// returns the verification token expected for commands issued
+    /// from the provided team (and enterprise) or None when the command
+    /// is not enabled for that team
+    fn token(&self, team_id: &TeamId, enterprise_id: Option<&EnterpriseId>) -> Option<String>;
 }
 
-impl<H: Handler + 'static> Handler for TokenValidator<H> {
-    fn handle(
-        &self,
-        cmd: &Command,
-        caps: &Option<Captures>,
-        responder: Box<Responder>,
-    ) -> Option<Response> {
-        if cmd.token == self.token {
-            self.handler.handle(cmd, caps, responder)
-        } else {
-            error!(
-                "cmd token ${:?} did not match handler token ${:?}",
-                cmd.token,
-                self.token
-            );
-            None
-        }
+/// Tokens keyed by team. Enterprises may be used as keys
+/// to cover all teams within an enterprise
+impl TokenLookup for HashMap<Workspace, String> {
+    fn token(&self, team_id: &TeamId, enterprise_id: Option<&EnterpriseId>) -> Option<String> {
+        self.get(&Workspace::Team(team_id.clone()))
+            .or_else(|| {
+                enterprise_id.and_then(|id| self.get(&Workspace::Enterprise(id.clone())))
+            })
+            .cloned()
     }
+}
 
-    fn as_handler(&self) -> &Handler {
-        self
+impl<F> TokenLookup for F
+where
+    F: Fn(&TeamId, Option<&EnterpriseId>) -> Option<String>,
+    F: Send + Sync,
+{
+    fn token(&self, team_id: &TeamId, enterprise_id: Option<&EnterpriseId>) -> Option<String> {
+        self(team_id, enterprise_id)
     }
 }
 
@@ -147,6 +257,24 @@ pub trait Matcher: Send + Sync {
     /// returns of tuple of optional captures and an indicator for
     /// whether or not the provided command is matched
     fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool);
+
+    /// returns the commands or subcommand words this matcher accepts, i.e. `/ops` or
+    /// `deploy`. Matchers built from patterns or predicates return none
+    fn names(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// returns a measure of how narrowly this matcher matches commands, used to
+    /// prefer specific routes over broad ones. Matchers of unknown specificity are `0`
+    fn specificity(&self) -> u32 {
+        0
+    }
+
+    /// returns true when this matcher is known to match every command. Routes
+    /// which a route with such a matcher is preferred to are never reached
+    fn always_matches(&self) -> bool {
+        false
+    }
 }
 
 impl<F> Matcher for F
@@ -159,55 +287,441 @@ where
     }
 }
 
+impl Matcher for Box<Matcher> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        (**self).matches(cmd)
+    }
+
+    fn names(&self) -> Vec<String> {
+        (**self).names()
+    }
+
+    fn specificity(&self) -> u32 {
+        (**self).specificity()
+    }
+
+    fn always_matches(&self) -> bool {
+        (**self).always_matches()
+    }
+}
+
 /// A direct command matcher
 pub struct MatchCommand(pub String);
 
+impl MatchCommand {
+    pub fn new<C>(command: C) -> MatchCommand
+    where
+        C: Into<String>,
+    {
+        MatchCommand(command.into())
+    }
+
+    /// returns a matcher for this command regardless of case
+    pub fn ignore_case(self) -> IgnoreCase<MatchCommand> {
+        IgnoreCase(self)
+    }
+}
+
 impl Matcher for MatchCommand {
     fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
         (None, cmd.command == self.0)
     }
+
+    fn names(&self) -> Vec<String> {
+        vec![self.0.clone()]
+    }
+
+    fn specificity(&self) -> u32 {
+        2
+    }
+}
+
+/// A matcher for a command or subcommand regardless of case. See
+/// `MatchCommand::ignore_case` and `MatchSubCommand::ignore_case`
+pub struct IgnoreCase<M>(pub M);
+
+impl Matcher for IgnoreCase<MatchCommand> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        (None, cmd.command.to_lowercase() == (self.0).0.to_lowercase())
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.names()
+    }
+
+    fn specificity(&self) -> u32 {
+        self.0.specificity()
+    }
+}
+
+impl Matcher for IgnoreCase<MatchSubCommand> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        (None, word_prefix(&cmd.text, &(self.0).0, true).is_some())
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.names()
+    }
+
+    fn specificity(&self) -> u32 {
+        self.0.specificity()
+    }
+}
+
+/// A command matcher which only matches commands issued from a given
+/// team. Commands issued from teams within an enterprise also match when
+/// the enterprise is provided
+pub struct MatchTeamCommand(pub String, pub Workspace);
+
+impl Matcher for MatchTeamCommand {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        let team = self.1.contains(&cmd.team_id, cmd.enterprise_id.as_ref());
+        (None, cmd.command == self.0 && team)
+    }
+
+    fn names(&self) -> Vec<String> {
+        vec![self.0.clone()]
+    }
+
+    fn specificity(&self) -> u32 {
+        3
+    }
+}
+
+/// A command matcher which only matches commands issued from teams
+/// the provided lookup has a token for
+struct MatchEnabledCommand<L: TokenLookup>(String, Arc<L>);
+
+impl<L: TokenLookup> Matcher for MatchEnabledCommand<L> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        let enabled = cmd.command == self.0 &&
+            self.1
+                .token(&cmd.team_id, cmd.enterprise_id.as_ref())
+                .is_some();
+        (None, enabled)
+    }
+
+    fn names(&self) -> Vec<String> {
+        vec![self.0.clone()]
+    }
+
+    fn specificity(&self) -> u32 {
+        3
+    }
+}
+
+/// Returns the length in bytes of `prefix` at the start of `text` when
+/// it is followed by whitespace or the end of the text
+fn word_prefix(text: &str, prefix: &str, ignore_case: bool) -> Option<usize> {
+    let mut chars = text.char_indices();
+    for expected in prefix.chars() {
+        match chars.next() {
+            Some((_, c)) if c == expected ||
+                                ignore_case && c.to_lowercase().eq(expected.to_lowercase()) => (),
+            _ => return None,
+        }
+    }
+    match chars.next() {
+        None => Some(text.len()),
+        Some((idx, c)) if c.is_whitespace() => Some(idx),
+        _ => None,
+    }
 }
 
-/// A matcher that assumes any text starting with
-/// the provided string is a subcommand. i.e. /cmd help
+/// A matcher that assumes text starting with the provided
+/// words is a subcommand. i.e. /cmd help. Text must start with
+/// whole words, so `deploy` does not match `deployments`
 pub struct MatchSubCommand(pub String);
 
+impl MatchSubCommand {
+    pub fn new<S>(subcommand: S) -> MatchSubCommand
+    where
+        S: Into<String>,
+    {
+        MatchSubCommand(subcommand.into())
+    }
+
+    /// returns a matcher for this subcommand regardless of case
+    pub fn ignore_case(self) -> IgnoreCase<MatchSubCommand> {
+        IgnoreCase(self)
+    }
+}
+
 impl Matcher for MatchSubCommand {
     fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
-        if cmd.text.starts_with(&self.0) {
+        if word_prefix(&cmd.text, &self.0, false).is_some() {
             (None, true)
         } else {
-            debug!("regex {:?} did not match cmd {:?}", self.0, cmd.command);
+            debug!(
+                "subcommand {:?} did not match cmd {:?}",
+                self.0,
+                cmd.command
+            );
             (None, false)
         }
     }
+
+    fn names(&self) -> Vec<String> {
+        vec![self.0.clone()]
+    }
+
+    fn specificity(&self) -> u32 {
+        2
+    }
 }
 
 /// A regex pattern matcher for command text.
 /// Regex captures will be provided to the matched Handler
 pub struct MatchText(pub Regex);
 
+impl MatchText {
+    pub fn new(regex: Regex) -> MatchText {
+        MatchText(regex)
+    }
+
+    /// builds a matcher for text regardless of case, keeping
+    /// any other options configured on the builder
+    pub fn ignore_case(mut builder: RegexBuilder) -> result::Result<MatchText, regex::Error> {
+        builder.case_insensitive(true);
+        builder.build().map(MatchText)
+    }
+}
+
 impl Matcher for MatchText {
     fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
         if self.0.is_match(cmd.text.as_ref()) {
-            (self.0.captures(cmd.text.as_ref()), true)
+            (Captures::from_regex(&self.0, &cmd.text), true)
         } else {
             debug!("regex {:?} did not match cmd {:?}", self.0, cmd.command);
             (None, false)
         }
     }
+
+    fn specificity(&self) -> u32 {
+        1
+    }
+
+    /// a regex is assumed to match all text when it matches an empty
+    /// string at the start of text regardless of the text which follows
+    fn always_matches(&self) -> bool {
+        ["", "a", "A", "0", " ", "\n", "-"]
+            .iter()
+            .all(|text| self.0.shortest_match(text) == Some(0))
+    }
 }
 
-#[doc(hidden)]
+/// Descriptive information about a route, used to document the commands a Mux serves
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RouteInfo {
+    /// a short name for the route, i.e. `deploy`
+    pub name: Option<String>,
+    /// a description of what the route's commands do
+    pub description: Option<String>,
+    /// a hint for the arguments the route's commands expect, i.e. `<service> [--force]`
+    pub usage: Option<String>,
+    /// the team which owns the route
+    pub team: Option<String>,
+    /// whether Slack should escape channels, users, and links in the route's command text
+    pub escape: bool,
+    /// the roles permitted to invoke the route, if restricted
+    pub roles: Vec<String>,
+}
+
+/// A Matcher and the Handler it routes commands to
 pub struct Route {
     handler: Box<Handler>,
     matcher: Box<Matcher>,
+    priority: i32,
+    info: RouteInfo,
+    verification: Vec<Box<Middleware>>,
+    middleware: Vec<Box<Middleware>>,
+    confirm: Option<Confirmation>,
+    interactive: bool,
+}
+
+impl Route {
+    pub fn new<M, H>(matcher: M, handler: H) -> Route
+    where
+        M: Matcher + 'static,
+        H: Handler + 'static,
+    {
+        Route {
+            handler: Box::new(handler),
+            matcher: Box::new(matcher),
+            priority: 0,
+            info: RouteInfo::default(),
+            verification: vec![],
+            middleware: vec![],
+            confirm: None,
+            interactive: false,
+        }
+    }
+
+    /// Install middleware which runs around this route's handler, after the
+    /// route's token verification and any middleware installed on the Mux.
+    /// Middleware runs in the order it is installed
+    pub fn middleware<M>(&mut self, middleware: M) -> &mut Route
+    where
+        M: Middleware + 'static,
+    {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Install middleware which verifies that commands were sent by Slack.
+    /// Verification runs before any other middleware
+    fn verify_with<M>(&mut self, middleware: M) -> &mut Route
+    where
+        M: Middleware + 'static,
+    {
+        self.verification.push(Box::new(middleware));
+        self
+    }
+
+    /// runs this route's verification for a command
+    fn verify(&self, cmd: &Command, params: &Params) -> Flow {
+        for verification in self.verification.iter() {
+            if let Flow::Respond(response) = verification.before(cmd, params) {
+                return Flow::Respond(response);
+            }
+        }
+        Flow::Continue
+    }
+
+    /// Names this route
+    pub fn name<N>(&mut self, name: N) -> &mut Route
+    where
+        N: Into<String>,
+    {
+        self.info.name = Some(name.into());
+        self
+    }
+
+    /// Describes what this route's commands do
+    pub fn description<D>(&mut self, description: D) -> &mut Route
+    where
+        D: Into<String>,
+    {
+        self.info.description = Some(description.into());
+        self
+    }
+
+    /// Describes the arguments this route's commands expect
+    pub fn usage<U>(&mut self, usage: U) -> &mut Route
+    where
+        U: Into<String>,
+    {
+        self.info.usage = Some(usage.into());
+        self
+    }
+
+    /// Names the team which owns this route
+    pub fn team<T>(&mut self, team: T) -> &mut Route
+    where
+        T: Into<String>,
+    {
+        self.info.team = Some(team.into());
+        self
+    }
+
+    /// Requests that Slack escape channels, users, and links in this
+    /// route's command text so they may be parsed with `Command::entities`
+    pub fn escape(&mut self) -> &mut Route {
+        self.info.escape = true;
+        self
+    }
+
+    /// Restricts this route to users holding at least one of the provided
+    /// roles, as resolved by a RoleProvider. Other users receive a denial
+    pub fn roles<P, I, R>(&mut self, provider: Arc<P>, roles: I) -> &mut Route
+    where
+        P: RoleProvider + 'static,
+        I: IntoIterator<Item = R>,
+        R: Into<String>,
+    {
+        let require = RequireRoles::new(provider, roles);
+        self.info.roles.extend(require.roles().iter().cloned());
+        self.middleware(require)
+    }
+
+    /// Asks users to confirm this route's commands before they are handled.
+    /// Commands are answered with an ephemeral prompt and only handled once the
+    /// user who issued them confirms within the timeout. `{}` in the prompt is
+    /// replaced with the command as it was invoked
+    pub fn confirm<P>(&mut self, prompt: P, timeout: Duration) -> &mut Route
+    where
+        P: Into<String>,
+    {
+        self.confirm = Some(Confirmation {
+            prompt: prompt.into(),
+            timeout: timeout,
+        });
+        self.interactive = true;
+        self
+    }
+
+    /// returns descriptive information about this route
+    pub fn info(&self) -> &RouteInfo {
+        &self.info
+    }
+
+    /// returns the command names this route matches, including the
+    /// subcommands its handler dispatches to, i.e. `/ops` and `/ops deploy`
+    pub fn commands(&self) -> Vec<String> {
+        let subcommands = self.handler.subcommands();
+        let mut commands = vec![];
+        for name in self.matcher.names() {
+            for subcommand in subcommands.iter() {
+                commands.push(format!("{} {}", name, subcommand));
+            }
+            commands.push(name);
+        }
+        commands
+    }
+
+    /// Routes with higher priorities are preferred over those with lower
+    /// priorities when more than one route matches a command. Routes have a
+    /// priority of `0` by default
+    pub fn priority(&mut self, priority: i32) -> &mut Route {
+        self.priority = priority;
+        self
+    }
+
+    /// returns true when this route is preferred over another route registered before it
+    fn outranks(&self, earlier: &Route, most_specific: bool) -> bool {
+        self.priority > earlier.priority ||
+            self.priority == earlier.priority && most_specific &&
+                self.matcher.specificity() > earlier.matcher.specificity()
+    }
 }
 
 /// A command de-multiplexor
-#[derive(Default)]
 pub struct Mux {
     routes: Vec<Box<Route>>,
+    middleware: Vec<Box<Middleware>>,
+    state: State,
+    pending: Arc<PendingActions>,
+    actions: Option<Box<ActionHandler>>,
+    fallback: Option<Box<Handler>>,
+    ssl_check_token: Option<String>,
+    most_specific: bool,
+    validated: Once,
+}
+
+impl Default for Mux {
+    fn default() -> Mux {
+        Mux {
+            routes: vec![],
+            middleware: vec![],
+            state: State::new(),
+            pending: Arc::new(MemoryPendingActions::new()),
+            actions: None,
+            fallback: None,
+            ssl_check_token: None,
+            most_specific: false,
+            validated: Once::new(),
+        }
+    }
 }
 
 impl Mux {
@@ -216,125 +730,530 @@ impl Mux {
     }
 
     /// Install routing for a Slack command, secret token, and target Handler
-    pub fn command<C, T, H>(&mut self, cmd: C, token: T, handler: H)
+    pub fn command<C, T, H>(&mut self, cmd: C, token: T, handler: H) -> &mut Route
     where
         C: Into<String>,
         T: Into<String>,
         H: Handler + 'static,
     {
-        self.matching(
-            MatchCommand(cmd.into()),
-            TokenValidator {
-                handler: handler,
-                token: token.into(),
-            },
-        )
+        self.matching(MatchCommand::new(cmd), handler)
+            .verify_with(TokenValidator::new(token))
+    }
+
+    /// Install routing for a Slack command issued from a specific team
+    /// (or enterprise), secret token, and target Handler. This allows the same
+    /// command to be served by different handlers in different workspaces
+    pub fn team_command<C, M, T, H>(&mut self, cmd: C, team: M, token: T, handler: H) -> &mut Route
+    where
+        C: Into<String>,
+        M: Into<Workspace>,
+        T: Into<String>,
+        H: Handler + 'static,
+    {
+        self.matching(MatchTeamCommand(cmd.into(), team.into()), handler)
+            .verify_with(TokenValidator::new(token))
+    }
+
+    /// Install routing for a Slack command whose secret tokens are resolved
+    /// per team by a TokenLookup. The command is only routed for teams the lookup
+    /// provides a token for
+    pub fn command_with<C, L, H>(&mut self, cmd: C, lookup: L, handler: H) -> &mut Route
+    where
+        C: Into<String>,
+        L: TokenLookup + 'static,
+        H: Handler + 'static,
+    {
+        let lookup = Arc::new(lookup);
+        self.matching(MatchEnabledCommand(cmd.into(), lookup.clone()), handler)
+            .verify_with(TeamTokenValidator::new(lookup))
+    }
+
+    /// Verify the token Slack sends with `ssl_check` probes. By default
+    /// probes are acknowledged without verification
+    pub fn ssl_check_token<T>(&mut self, token: T)
+    where
+        T: Into<String>,
+    {
+        self.ssl_check_token = Some(token.into());
+    }
+
+    /// Returns None when params do not represent an ssl_check probe,
+    /// otherwise returns whether or not the probe is valid
+    fn ssl_check(&self, params: &HashMap<String, String>) -> Option<bool> {
+        if !params.get("ssl_check").map_or(false, |value| truthy(value)) {
+            return None;
+        }
+        Some(match self.ssl_check_token {
+            Some(ref token) => params.get("token") == Some(token),
+            _ => true,
+        })
     }
 
     /// Install routing for a Slack command matcher and target Handler
-    pub fn matching<M, H>(&mut self, matcher: M, handler: H)
+    pub fn matching<M, H>(&mut self, matcher: M, handler: H) -> &mut Route
     where
         M: Matcher + 'static,
         H: Handler + 'static,
     {
-        let route = Route {
-            handler: Box::new(handler),
-            matcher: Box::new(matcher),
-        };
-        self.route(route)
+        self.route(Route::new(matcher, handler))
     }
 
     /// Install a command routing
-    pub fn route(&mut self, route: Route) {
+    pub fn route(&mut self, route: Route) -> &mut Route {
         self.routes.push(Box::new(route));
+        self.routes.last_mut().unwrap()
     }
 
-    /// Attempts to return the first match result for a target Handler
-    pub fn handler<'a>(&self, cmd: &'a Command) -> Option<(Option<Captures<'a>>, &Box<Handler>)> {
-        for r in self.routes.iter() {
-            if let (captures, true) = r.matcher.matches(cmd) {
-                return Some((captures, &r.handler));
+    /// Fails with `ErrorKind::UnreachableRoute` when a route can never be
+    /// reached because a route which matches every command is always preferred
+    /// to it. Call this once routes and their priorities are configured. The
+    /// same problem is otherwise logged as a warning when the first command is handled
+    pub fn validate(&self) -> Result<()> {
+        for (index, route) in self.routes.iter().enumerate() {
+            let shadowed = self.routes.iter().enumerate().find(|&(other, catchall)| {
+                other != index && catchall.matcher.always_matches() &&
+                    if other < index {
+                        !route.outranks(catchall, self.most_specific)
+                    } else {
+                        catchall.outranks(route, self.most_specific)
+                    }
+            });
+            if let Some((_, catchall)) = shadowed {
+                return Err(
+                    ErrorKind::UnreachableRoute(route.matcher.names(), catchall.matcher.names())
+                        .into(),
+                );
             }
         }
-        None
+        Ok(())
     }
-}
 
-impl Handler for Mux {
-    fn handle(
-        &self,
-        cmd: &Command,
-        _: &Option<Captures>,
-        responder: Box<Responder>,
-    ) -> Option<Response> {
-        if let &Some((ref captures, handler)) = &self.handler(&cmd) {
-            debug!("cmd matched. attempting to handle cmd {:#?}", cmd);
-            handler.handle(&cmd, &captures, responder)
-        } else {
-            debug!("no matching handlers for {:#?}", cmd);
-            None
-        }
+    /// Prefer the most specific matching route when more than one route of
+    /// the same priority matches a command. By default the first matching route
+    /// of the highest priority is preferred
+    pub fn most_specific(&mut self) {
+        self.most_specific = true;
     }
 
-    fn as_handler(&self) -> &Handler {
-        self
+    /// Install a Handler for commands no route matches. The closest registered
+    /// command or subcommand name, if any, is provided as the `suggestion` param.
+    /// `DidYouMean` provides a default reply
+    pub fn fallback<H>(&mut self, handler: H)
+    where
+        H: Handler + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
     }
-}
 
-/// A struct representation of a Slack Command
-/// and the context from which it was triggered
-#[derive(Default, Debug, PartialEq)]
-pub struct Command {
-    pub token: String,
-    pub team_id: String,
+    /// Install routing for a child Mux under a prefix of a command followed
+    /// by optional subcommand words, i.e. `/ops` or `/ops deploy`, or subcommand
+    /// words alone, i.e. `deploy`. The child sees command text with the prefix's
+    /// subcommand words removed and replies with its own help when the remaining
+    /// text is empty, `help`, or matches none of its routes
+    pub fn mount<P>(&mut self, prefix: P, child: Mux) -> &mut Route
+    where
+        P: Into<String>,
+    {
+        let mount = Mount::new(prefix.into(), child);
+        let matcher: Box<Matcher> = match (mount.command(), mount.words()) {
+            (Some(command), "") => Box::new(MatchCommand::new(command)),
+            (Some(command), words) => {
+                Box::new(MatchCommand::new(command).and(MatchSubCommand::new(words)))
+            }
+            (None, words) => Box::new(MatchSubCommand::new(words)),
+        };
+        let verification = mount.verification();
+        let interactive = mount.child().interactive();
+        let route = self.matching(matcher, mount).verify_with(verification);
+        route.interactive = interactive;
+        route
+    }
+
+    /// returns true when this Mux answers interactive message payloads, either
+    /// for the confirmation prompts of its routes or with an `ActionHandler`
+    pub fn interactive(&self) -> bool {
+        self.actions.is_some() || self.routes.iter().any(|route| route.interactive)
+    }
+
+    /// Returns an ephemeral response listing the commands this Mux's routes
+    /// serve. Names of subcommand routes are listed after the provided prefix
+    pub fn help(&self, prefix: &str) -> Response {
+        let mut lines = vec![format!("*{}* commands", prefix)];
+        for route in self.routes.iter() {
+            for name in route.matcher.names() {
+                let mut line = if name.starts_with('/') {
+                    format!("`{}", name)
+                } else {
+                    format!("`{} {}", prefix, name)
+                };
+                if let Some(ref usage) = route.info.usage {
+                    line.push(' ');
+                    line.push_str(usage);
+                }
+                line.push('`');
+                if let Some(ref description) = route.info.description {
+                    line.push_str(" - ");
+                    line.push_str(description);
+                }
+                lines.push(line);
+            }
+        }
+        lines.push(format!("`{} help` - lists these commands", prefix));
+        Response::ephemeral(lines.join("\n"))
+    }
+
+    /// returns the command and subcommand names of all routes
+    fn names(&self) -> Vec<String> {
+        self.routes.iter().flat_map(|route| route.commands()).collect()
+    }
+
+    /// returns the installed routes in the order they were registered
+    pub fn routes<'a>(&'a self) -> Box<Iterator<Item = &'a Route> + 'a> {
+        Box::new(self.routes.iter().map(|route| &**route))
+    }
+
+    /// Register application state which handlers may look up by type
+    /// with `Context::state`. State registered on a mounted Mux takes
+    /// precedence over its parent's state
+    pub fn state<T>(&mut self, value: T)
+    where
+        T: Any + Send + Sync,
+    {
+        self.state.insert(value);
+    }
+
+    /// Store commands awaiting confirmation somewhere other than memory, i.e.
+    /// when confirmations may be answered by another instance of an app.
+    /// Mounted Muxes share the store of the Mux which received the command
+    pub fn pending_actions<P>(&mut self, pending: P)
+    where
+        P: PendingActions + 'static,
+    {
+        self.pending = Arc::new(pending);
+    }
+
+    /// Install a handler for interactive message payloads which do not
+    /// answer a confirmation prompt, i.e. clicks on buttons attached to an
+    /// app's own responses. Without one such payloads are acknowledged
+    /// without a reply
+    pub fn actions<H>(&mut self, handler: H)
+    where
+        H: ActionHandler + 'static,
+    {
+        self.actions = Some(Box::new(handler));
+    }
+
+    /// Install middleware which runs around every command this Mux
+    /// handles. Middleware runs in the order it is installed, after the
+    /// matched route's token verification. Commands which no route matches
+    /// are not verified before they reach middleware and the fallback
+    pub fn middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware + 'static,
+    {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    /// Attempts to return the preferred match result for a target Handler. The
+    /// Handler runs behind the route's token verification and middleware
+    pub fn handler<'a, 'm>(
+        &'m self,
+        cmd: &'a Command,
+    ) -> Option<(Option<Captures<'a>>, RouteHandler<'m>)> {
+        self.preferred(cmd).map(|(captures, route)| {
+            (
+                captures,
+                RouteHandler {
+                    mux: self,
+                    route: route,
+                },
+            )
+        })
+    }
+
+    /// runs a route's handler behind its token verification, this
+    /// Mux's middleware, the route's middleware, and its confirmation.
+    /// Confirmed commands already passed through middleware when they were
+    /// issued, so they are only verified before they reach the handler
+    fn run_route(&self, route: &Route, ctx: Context) -> Option<Response> {
+        let confirm;
+        let mut chain: Vec<&Middleware> = route.verification.iter().map(|m| &**m).collect();
+        if ctx.params.name(confirm::CONFIRMED).is_some() {
+            return middleware::run(&chain, ctx, &*route.handler);
+        }
+        chain.extend(self.middleware.iter().map(|m| &**m));
+        chain.extend(route.middleware.iter().map(|m| &**m));
+        if let (Some(confirmation), Some(pending)) =
+            (route.confirm.as_ref(), ctx.pending_actions())
+        {
+            confirm = Confirm::new(confirmation, pending, ctx.origin());
+            chain.push(&confirm);
+        }
+        middleware::run(&chain, ctx, &*route.handler)
+    }
+
+    /// returns the preferred matching route
+    fn preferred<'a>(&self, cmd: &'a Command) -> Option<(Option<Captures<'a>>, &Route)> {
+        let mut preferred: Option<(Option<Captures<'a>>, &Route)> = None;
+        for r in self.routes.iter() {
+            if let Some(&(_, best)) = preferred.as_ref() {
+                if !r.outranks(best, self.most_specific) {
+                    continue;
+                }
+            }
+            if let (captures, true) = r.matcher.matches(cmd) {
+                preferred = Some((captures, r));
+            }
+        }
+        preferred
+    }
+}
+
+impl Handler for Mux {
+    fn handle(
+        &self,
+        cmd: &Command,
+        params: &Params,
+        responder: Box<Responder>,
+    ) -> Option<Response> {
+        let request = RequestInfo::default();
+        self.handle_context(Context::new(cmd, params, responder, &request))
+    }
+
+    fn handle_context(&self, ctx: Context) -> Option<Response> {
+        self.validated.call_once(|| if let Err(err) = self.validate() {
+            warn!("{}", err);
+        });
+        let cmd = ctx.command;
+        let ctx = ctx.with_state(&self.state).with_pending_actions(
+            &*self.pending,
+        );
+        if let Some((captures, route)) = self.preferred(cmd) {
+            debug!("cmd matched. attempting to handle cmd {:#?}", cmd);
+            let mut params = Params::from(captures);
+            params.merge(ctx.params);
+            self.run_route(route, ctx.with_command(cmd, &params))
+        } else if let Some(ref fallback) = self.fallback {
+            debug!("no matching handlers for {:#?}. falling back", cmd);
+            let mut params = ctx.params.clone();
+            if let Some(suggestion) = fallback::suggest(cmd, &self.names()) {
+                params.insert("suggestion", suggestion);
+            }
+            let chain: Vec<&Middleware> = self.middleware.iter().map(|m| &**m).collect();
+            middleware::run(&chain, ctx.with_command(cmd, &params), &**fallback)
+        } else {
+            debug!("no matching handlers for {:#?}", cmd);
+            None
+        }
+    }
+
+    fn as_handler(&self) -> &Handler {
+        self
+    }
+}
+
+/// The Handler of a route matched by a Mux, which runs behind the route's
+/// token verification, the Mux's middleware, and the route's middleware
+pub struct RouteHandler<'a> {
+    mux: &'a Mux,
+    route: &'a Route,
+}
+
+impl<'a> RouteHandler<'a> {
+    /// returns the matched route
+    pub fn route(&self) -> &'a Route {
+        self.route
+    }
+}
+
+impl<'a> Handler for RouteHandler<'a> {
+    fn handle(
+        &self,
+        cmd: &Command,
+        params: &Params,
+        responder: Box<Responder>,
+    ) -> Option<Response> {
+        let request = RequestInfo::default();
+        self.handle_context(Context::new(cmd, params, responder, &request))
+    }
+
+    fn handle_context(&self, ctx: Context) -> Option<Response> {
+        let ctx = ctx.with_state(&self.mux.state).with_pending_actions(
+            &*self.mux.pending,
+        );
+        self.mux.run_route(self.route, ctx)
+    }
+
+    fn as_handler(&self) -> &Handler {
+        self
+    }
+
+    fn subcommands(&self) -> Vec<String> {
+        self.route.handler.subcommands()
+    }
+}
+
+/// A struct representation of a Slack Command
+/// and the context from which it was triggered
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Command {
+    pub token: String,
+    pub team_id: TeamId,
     pub team_domain: String,
-    pub channel_id: String,
+    pub enterprise_id: Option<EnterpriseId>,
+    pub enterprise_name: Option<String>,
+    pub channel_id: ChannelId,
     pub channel_name: String,
-    pub user_id: String,
+    pub user_id: UserId,
     pub user_name: String,
     pub command: String,
     pub text: String,
     pub response_url: String,
+    pub api_app_id: Option<String>,
+    pub trigger_id: Option<String>,
+    pub is_enterprise_install: bool,
+    pub ssl_check: bool,
+    /// any additional parameters Slack sends which are not yet
+    /// captured by the fields above
+    pub extra: HashMap<String, String>,
+}
+
+/// removes an optional parameter, treating empty values as absent
+fn optional(params: &mut HashMap<String, String>, name: &str) -> Option<String> {
+    params.remove(name).and_then(
+        |value| if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        },
+    )
+}
+
+/// removes a boolean parameter. Slack encodes these as either `true` or `1`
+fn flag(params: &mut HashMap<String, String>, name: &str) -> bool {
+    params
+        .remove(name)
+        .map(|value| truthy(&value))
+        .unwrap_or(false)
+}
+
+/// returns true if the value of a boolean parameter is set
+fn truthy(value: &str) -> bool {
+    value == "true" || value == "1"
+}
+
+/// removes a parameter which must be present and non-empty, noting it as missing otherwise
+fn required(params: &mut HashMap<String, String>, name: &str, missing: &mut Vec<String>) -> String {
+    match params.remove(name) {
+        Some(ref value) if !value.is_empty() => value.clone(),
+        _ => {
+            missing.push(name.to_owned());
+            String::new()
+        }
+    }
+}
+
+/// parses an id from a parameter value, noting it as invalid on failure.
+/// Empty values are assumed to already be noted as missing
+fn id<I>(value: String, name: &str, invalid: &mut Vec<String>) -> I
+where
+    I: FromStr + Default,
+{
+    if value.is_empty() {
+        return I::default();
+    }
+    value.parse().unwrap_or_else(|_| {
+        invalid.push(name.to_owned());
+        I::default()
+    })
+}
+
+/// strips leading whitespace from text, including the zero width
+/// spaces and byte order marks some clients prepend
+fn normalize(text: String) -> String {
+    let skip = |c: char| c.is_whitespace() || c == '\u{200B}' || c == '\u{2060}' || c == '\u{FEFF}';
+    match text.char_indices().find(|&(_, c)| !skip(c)) {
+        Some((0, _)) => text,
+        Some((idx, _)) => text[idx..].to_owned(),
+        None => String::new(),
+    }
 }
 
 impl Command {
-    pub fn from_params(params: HashMap<String, String>) -> Option<Command> {
-        if let (Some(token),
-                Some(team_id),
-                Some(team_domain),
-                Some(channel_id),
-                Some(channel_name),
-                Some(user_id),
-                Some(user_name),
-                Some(command),
-                Some(text),
-                Some(response_url)) =
-            (params.get("token"),
-             params.get("team_id"),
-             params.get("team_domain"),
-             params.get("channel_id"),
-             params.get("channel_name"),
-             params.get("user_id"),
-             params.get("user_name"),
-             params.get("command"),
-             params.get("text"),
-             params.get("response_url")) {
-            Some(
-                Command {
-                    token: token.clone(),
-                    team_id: team_id.clone(),
-                    team_domain: team_domain.clone(),
-                    channel_id: channel_id.clone(),
-                    channel_name: channel_name.clone(),
-                    user_id: user_id.clone(),
-                    user_name: user_name.clone(),
-                    command: command.clone(),
-                    text: text.clone(),
-                    response_url: response_url.clone(),
-                },
-            )
+    /// Tokenizes the command's text into shell-style arguments
+    pub fn args(&self) -> Result<Args> {
+        Args::parse(&self.text)
+    }
+
+    /// Extracts typed arguments from the command's text. On failure an
+    /// ephemeral response describing the problem and expected usage is returned
+    pub fn parse<T>(&self) -> ::std::result::Result<T, Response>
+    where
+        T: CommandArgs,
+    {
+        self.args().and_then(|args| T::from_args(&args)).map_err(|err| {
+            let reason = match *err.kind() {
+                ErrorKind::InvalidArgs(ref reason) => reason.clone(),
+                _ => err.to_string(),
+            };
+            Response::ephemeral(format!(
+                "{}\nusage: `{} {}`",
+                reason,
+                self.command,
+                T::usage()
+            ))
+        })
+    }
+
+    /// Parses a command from request parameters, returning an
+    /// `ErrorKind::InvalidCommand` naming any missing or invalid fields.
+    /// `team_domain`, `channel_name`, `user_name` and `text` are not always
+    /// provided by Slack and default to empty strings
+    pub fn from_params(mut params: HashMap<String, String>) -> Result<Command> {
+        let mut missing = vec![];
+        let mut invalid = vec![];
+        let cmd = Command {
+            token: required(&mut params, "token", &mut missing),
+            team_id: id(
+                required(&mut params, "team_id", &mut missing),
+                "team_id",
+                &mut invalid,
+            ),
+            team_domain: params.remove("team_domain").unwrap_or_default(),
+            enterprise_id: optional(&mut params, "enterprise_id")
+                .map(|value| id(value, "enterprise_id", &mut invalid)),
+            enterprise_name: optional(&mut params, "enterprise_name"),
+            channel_id: id(
+                required(&mut params, "channel_id", &mut missing),
+                "channel_id",
+                &mut invalid,
+            ),
+            channel_name: params.remove("channel_name").unwrap_or_default(),
+            user_id: id(
+                required(&mut params, "user_id", &mut missing),
+                "user_id",
+                &mut invalid,
+            ),
+            user_name: params.remove("user_name").unwrap_or_default(),
+            command: required(&mut params, "command", &mut missing),
+            text: params.remove("text").map(normalize).unwrap_or_default(),
+            response_url: required(&mut params, "response_url", &mut missing),
+            api_app_id: optional(&mut params, "api_app_id"),
+            trigger_id: optional(&mut params, "trigger_id"),
+            is_enterprise_install: flag(&mut params, "is_enterprise_install"),
+            ssl_check: flag(&mut params, "ssl_check"),
+            extra: params,
+        };
+        if !cmd.command.is_empty() && !cmd.command.starts_with('/') {
+            invalid.push("command".to_owned());
+        }
+        if !cmd.response_url.is_empty() && url::Url::parse(&cmd.response_url).is_err() {
+            invalid.push("response_url".to_owned());
+        }
+        if missing.is_empty() && invalid.is_empty() {
+            Ok(cmd)
         } else {
-            None
+            Err(ErrorKind::InvalidCommand(missing, invalid).into())
         }
     }
 }
@@ -342,39 +1261,153 @@ impl Command {
 impl HyperHandler for Mux {
     // https://api.slack.com/slash-commands
     fn handle(&self, req: Request, mut res: HyperResponse) {
-        let (_, _, _, _, _, mut body) = req.deconstruct();
+        let (remote_addr, _, headers, _, _, mut body) = req.deconstruct();
 
         // parse params
         let params = params(&mut body);
-        // parse cmd
-        if let Some(cmd) = Command::from_params(params) {
-            debug!("rec cmd {:?}", cmd);
-            let write = |bytes: &[u8], content_type: ContentType| {
-                res.headers_mut().set(content_type);
-                let _ = res.send(bytes);
-            };
-            let responder = DefaultResponder::new(cmd.response_url.clone());
-            if let Some(resp) = self.as_handler()
-                   .handle(&cmd, &None, Box::new(responder)) {
-                match serde_json::to_string(&resp) {
-                    Ok(payload) => write(payload.as_bytes(), ContentType::json()),
-                    _ => write(DEFAULT_RESPONSE, ContentType::plaintext()),
-                };
+        // acknowledge ssl_check probes
+        // https://api.slack.com/slash-commands#ssl
+        if let Some(valid) = self.ssl_check(&params) {
+            if valid {
+                debug!("rec ssl_check");
             } else {
-                write(DEFAULT_RESPONSE, ContentType::plaintext())
-            };
-        } else {
-            error!("rec invalid cmd");
+                debug!("rec ssl_check with unexpected token");
+                *res.status_mut() = StatusCode::Unauthorized;
+            }
             let _ = res.send(DEFAULT_RESPONSE);
+            return;
+        }
+        // answer interactive message actions
+        // https://api.slack.com/docs/message-buttons
+        if let Some(payload) = params.get("payload") {
+            match serde_json::from_str::<ActionPayload>(payload) {
+                Ok(payload) => {
+                    debug!("rec action payload {:?}", payload);
+                    let request = RequestInfo::new(Some(remote_addr), headers);
+                    match confirm::interact(self, &payload, &request)
+                        .and_then(|resp| serde_json::to_string(&resp).ok()) {
+                        Some(body) => {
+                            res.headers_mut().set(ContentType::json());
+                            let _ = res.send(body.as_bytes());
+                        }
+                        _ => {
+                            res.headers_mut().set(ContentType::plaintext());
+                            let _ = res.send(DEFAULT_RESPONSE);
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!("rec invalid action payload: {}", err);
+                    *res.status_mut() = StatusCode::BadRequest;
+                    res.headers_mut().set(ContentType::plaintext());
+                    let _ = res.send(err.to_string().as_bytes());
+                }
+            }
+            return;
+        }
+        // parse cmd
+        match Command::from_params(params) {
+            Ok(cmd) => {
+                debug!("rec cmd {:?}", cmd);
+                let write = |bytes: &[u8], content_type: ContentType| {
+                    res.headers_mut().set(content_type);
+                    let _ = res.send(bytes);
+                };
+                let responder = DefaultResponder::new(cmd.response_url.clone());
+                let request = RequestInfo::new(Some(remote_addr), headers);
+                let params = Params::new();
+                let ctx = Context::new(&cmd, &params, Box::new(responder), &request);
+                if let Some(resp) = self.as_handler().handle_context(ctx) {
+                    match serde_json::to_string(&resp) {
+                        Ok(payload) => write(payload.as_bytes(), ContentType::json()),
+                        _ => write(DEFAULT_RESPONSE, ContentType::plaintext()),
+                    };
+                } else {
+                    write(DEFAULT_RESPONSE, ContentType::plaintext())
+                };
+            }
+            Err(err) => {
+                error!("rec invalid cmd: {}", err);
+                *res.status_mut() = StatusCode::BadRequest;
+                res.headers_mut().set(ContentType::plaintext());
+                let _ = res.send(err.to_string().as_bytes());
+            }
         }
         ()
     }
 }
 
+/// Fixtures shared by unit tests across modules
+#[cfg(test)]
+mod testing {
+    use super::{ChannelId, Command, EnterpriseId, Params, Responder, Response, TeamId, UserId};
+
+    /// A Responder which discards delayed responses
+    pub struct NoopResponder;
+
+    impl Responder for NoopResponder {
+        fn respond(&self, _: Response) {}
+    }
+
+    /// A handler which never replies
+    pub fn ok(_: &Command, _: &Params, _: Box<Responder>) -> Option<Response> {
+        None
+    }
+
+    /// Starts building an invocation of `command`. Fields which are not set keep their defaults
+    pub fn command(command: &str) -> CommandBuilder {
+        CommandBuilder(Command {
+            command: command.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Builds a `Command`, panicking on invalid ids
+    pub struct CommandBuilder(Command);
+
+    impl CommandBuilder {
+        pub fn text(mut self, text: &str) -> CommandBuilder {
+            self.0.text = text.to_owned();
+            self
+        }
+
+        pub fn token(mut self, token: &str) -> CommandBuilder {
+            self.0.token = token.to_owned();
+            self
+        }
+
+        pub fn user(mut self, user_id: &str) -> CommandBuilder {
+            self.0.user_id = UserId::new(user_id).unwrap();
+            self
+        }
+
+        pub fn channel(mut self, channel_id: &str) -> CommandBuilder {
+            self.0.channel_id = ChannelId::new(channel_id).unwrap();
+            self
+        }
+
+        pub fn team(mut self, team_id: &str) -> CommandBuilder {
+            self.0.team_id = TeamId::new(team_id).unwrap();
+            self
+        }
+
+        pub fn enterprise(mut self, enterprise_id: Option<&str>) -> CommandBuilder {
+            self.0.enterprise_id = enterprise_id.map(|id| EnterpriseId::new(id).unwrap());
+            self
+        }
+
+        pub fn build(self) -> Command {
+            self.0
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::regex::Regex;
+    use testing::{NoopResponder, command};
+    use hyper::Server;
     use std::collections::HashMap;
 
     #[test]
@@ -405,38 +1438,878 @@ mod tests {
         }
     }
 
+    #[test]
+    fn matches_whole_subcommands() {
+        let cmd = Command {
+            text: "deploy api".to_owned(),
+            ..Default::default()
+        };
+        assert!(MatchSubCommand::new("deploy").matches(&cmd).1);
+        assert!(MatchSubCommand::new("deploy api").matches(&cmd).1);
+        assert!(!MatchSubCommand::new("dep").matches(&cmd).1);
+        assert!(!MatchSubCommand::new("deploy ap").matches(&cmd).1);
+        assert!(!MatchSubCommand::new("Deploy").matches(&cmd).1);
+        assert!(MatchSubCommand::new("Deploy").ignore_case().matches(&cmd).1);
+        assert!(!MatchSubCommand::new("Dep").ignore_case().matches(&cmd).1);
+    }
+
+    #[test]
+    fn matches_regardless_of_case() {
+        let cmd = Command {
+            command: "/Test".to_owned(),
+            text: "HELLO world".to_owned(),
+            ..Default::default()
+        };
+        assert!(!MatchCommand::new("/test").matches(&cmd).1);
+        assert!(MatchCommand::new("/test").ignore_case().matches(&cmd).1);
+        let text = MatchText::new(Regex::new(r"^hello (?P<name>\S+)$").unwrap());
+        assert!(!text.matches(&cmd).1);
+        let mut builder = RegexBuilder::new(r"^hello \s (?P<name>\S+) $");
+        builder.ignore_whitespace(true);
+        let (captures, matched) = MatchText::ignore_case(builder).unwrap().matches(&cmd);
+        assert!(matched, "cmd did not match");
+        assert_eq!(
+            captures.and_then(|caps| caps.name("name")).map(|m| m.as_str()),
+            Some("world")
+        );
+    }
+
+    #[test]
+    fn combines_matchers() {
+        let cmd = Command {
+            command: "/ops".to_owned(),
+            text: "deploy api".to_owned(),
+            user_id: UserId::new("U0ADMIN").unwrap(),
+            ..Default::default()
+        };
+        fn admin(cmd: &Command) -> (Option<Captures>, bool) {
+            (None, cmd.user_id == "U0ADMIN")
+        }
+        let deploy = MatchCommand::new("/ops")
+            .and(MatchText::new(Regex::new(r"^(?P<action>\S+)").unwrap()))
+            .and(MatchText::new(Regex::new(r"(?P<service>\S+)$").unwrap()))
+            .and(admin);
+        match deploy.matches(&cmd) {
+            (Some(caps), true) => {
+                assert_eq!(caps.name("action").map(|m| m.as_str()), Some("deploy"));
+                assert_eq!(caps.name("service").map(|m| m.as_str()), Some("api"));
+                assert_eq!(caps.get(0).map(|m| m.as_str()), Some("deploy"));
+            }
+            _ => assert!(false, "expected merged captures"),
+        }
+        assert!(!MatchCommand::new("/ops").and(admin.not()).matches(&cmd).1);
+        assert!(MatchCommand::new("/dev").or(admin).matches(&cmd).1);
+        assert!(!MatchCommand::new("/dev").or(admin.not()).matches(&cmd).1);
+    }
+
+    #[test]
+    fn combines_lists_of_matchers() {
+        let cmd = Command {
+            command: "/ops".to_owned(),
+            text: "restart api".to_owned(),
+            ..Default::default()
+        };
+        let (caps, matched) = matcher::any(vec![
+            Box::new(MatchText::new(Regex::new(r"^deploy (?P<service>\S+)").unwrap())),
+            Box::new(MatchText::new(Regex::new(r"^restart (?P<service>\S+)").unwrap())),
+            Box::new(MatchText::new(Regex::new(r"^(?P<action>\S+)").unwrap())),
+        ]).matches(&cmd);
+        assert!(matched, "cmd did not match");
+        let caps = caps.unwrap();
+        assert_eq!(caps.name("service").map(|m| m.as_str()), Some("api"));
+        assert_eq!(caps.name("action").map(|m| m.as_str()), Some("restart"));
+        assert_eq!(caps.sets().len(), 2);
+        assert!(
+            matcher::all(vec![
+                Box::new(MatchCommand::new("/ops")),
+                Box::new(MatchSubCommand::new("restart")),
+            ]).matches(&cmd)
+                .1
+        );
+        assert!(
+            !matcher::all(vec![
+                Box::new(MatchCommand::new("/ops")),
+                Box::new(MatchSubCommand::new("deploy")),
+            ]).matches(&cmd)
+                .1
+        );
+        assert!(!matcher::any(vec![]).matches(&cmd).1);
+    }
+
+    #[test]
+    fn matches_context() {
+        let cmd = Command {
+            team_id: TeamId::new("T0TEST").unwrap(),
+            channel_id: ChannelId::new("D0TEST").unwrap(),
+            user_id: UserId::new("U0TEST").unwrap(),
+            ..Default::default()
+        };
+        assert!(MatchUser::id(UserId::new("U0TEST").unwrap()).matches(&cmd).1);
+        assert!(!MatchUser::id(UserId::new("U0OTHER").unwrap()).matches(&cmd).1);
+        assert!(
+            MatchUser::any(vec![UserId::new("U0OTHER").unwrap(), UserId::new("U0TEST").unwrap()])
+                .matches(&cmd)
+                .1
+        );
+        assert!(MatchChannel::when(|id| id.as_str().ends_with("TEST")).matches(&cmd).1);
+        assert!(!MatchTeam::any(vec![]).matches(&cmd).1);
+        assert!(MatchTeam::id(TeamId::new("T0TEST").unwrap()).matches(&cmd).1);
+        assert!(MatchChannelKind::kind(ChannelKind::Direct).matches(&cmd).1);
+        assert!(
+            !MatchChannelKind::any(vec![ChannelKind::Public, ChannelKind::Private])
+                .matches(&cmd)
+                .1
+        );
+    }
+
+    fn handle(mux: &Mux, cmd: &Command) -> Option<String> {
+        mux.as_handler()
+            .handle(cmd, &Params::new(), Box::new(NoopResponder))
+            .and_then(|res| res.text)
+    }
+
+    #[test]
+    fn passes_params_to_handlers() {
+        let mut mux = Mux::new();
+        mux.matching(
+            MatchText::new(Regex::new(r"^scale (?P<service>\S+) (?P<count>\d+)$").unwrap()),
+            |_: &Command, params: &Params, _: Box<Responder>| {
+                let params = params.clone();
+                let scaled = ::std::thread::spawn(move || {
+                    format!(
+                        "scaled {} to {}",
+                        params.name("service").unwrap_or_default(),
+                        params.get::<u32>("count").unwrap_or_default()
+                    )
+                }).join()
+                    .unwrap();
+                Some(Response::ephemeral(scaled))
+            },
+        );
+        let cmd = Command {
+            text: "scale api 3".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(handle(&mux, &cmd), Some("scaled api to 3".to_owned()));
+    }
+
+    #[test]
+    fn falls_back_with_suggestions() {
+        let mut mux = Mux::new();
+        let ok = |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("ok"));
+        let mut ops = SubCommands::new();
+        ops.command("deploy", "<service>", "deploys a service", ok)
+            .alias("ship");
+        mux.command("/ops", "token", ops);
+        mux.matching(
+            MatchCommand::new("/weather").and(MatchSubCommand::new("forecast")),
+            ok,
+        );
+        let cmd = |name: &str, text: &str| command(name).token("token").text(text).build();
+        assert_eq!(handle(&mux, &cmd("/opps", "deploy")), None);
+        mux.fallback(DidYouMean);
+        assert_eq!(
+            handle(&mux, &cmd("/opps", "deploy")),
+            Some("Sorry, I don't understand `/opps deploy`. Did you mean `/ops deploy`?".to_owned())
+        );
+        assert_eq!(
+            handle(&mux, &cmd("/weather", "forcast")),
+            Some(
+                "Sorry, I don't understand `/weather forcast`. Did you mean `/weather forecast`?"
+                    .to_owned(),
+            )
+        );
+        assert_eq!(
+            handle(&mux, &cmd("/lunch", "")),
+            Some("Sorry, I don't understand `/lunch`".to_owned())
+        );
+        assert_eq!(
+            mux.names(),
+            vec!["/ops deploy", "/ops ship", "/ops", "/weather forecast"]
+        );
+    }
+
+    #[test]
+    fn mounts_child_muxes() {
+        let mut platform = Mux::new();
+        platform
+            .matching(
+                MatchSubCommand::new("api"),
+                |cmd: &Command, params: &Params, _: Box<Responder>| {
+                    Some(Response::ephemeral(format!(
+                        "{} {} in {}",
+                        cmd.command,
+                        cmd.text,
+                        params.name("env").unwrap_or("prod")
+                    )))
+                },
+            )
+            .usage("[version]")
+            .description("deploys the api");
+        let mut mux = Mux::new();
+        mux.mount("/ops deploy", platform);
+        let cmd = |text: &str| command("/ops").text(text).build();
+        let help = "*/ops deploy* commands\n\
+                    `/ops deploy api [version]` - deploys the api\n\
+                    `/ops deploy help` - lists these commands";
+        assert_eq!(
+            handle(&mux, &cmd("deploy api v2")),
+            Some("/ops api v2 in prod".to_owned())
+        );
+        assert_eq!(handle(&mux, &cmd("deploy")), Some(help.to_owned()));
+        assert_eq!(handle(&mux, &cmd("deploy help")), Some(help.to_owned()));
+        assert_eq!(
+            handle(&mux, &cmd("deploy web")),
+            Some(format!("Unknown command `/ops deploy web`\n{}", help))
+        );
+        assert_eq!(handle(&mux, &cmd("deployments")), None);
+        assert_eq!(mux.names(), vec!["/ops deploy api", "/ops deploy"]);
+
+        let mut platform = Mux::new();
+        platform.matching(
+            MatchSubCommand::new("api"),
+            |cmd: &Command, params: &Params, _: Box<Responder>| {
+                Some(Response::ephemeral(
+                    format!("{} in {}", cmd.text, params.name("env").unwrap_or("prod")),
+                ))
+            },
+        );
+        let mut nested = Mux::new();
+        nested.mount("deploy", platform);
+        let mut parent = Mux::new();
+        parent.matching(
+            MatchText::new(Regex::new(r"^(?P<env>staging) ").unwrap()),
+            move |cmd: &Command, params: &Params, responder: Box<Responder>| {
+                let cmd = Command {
+                    text: cmd.text["staging ".len()..].to_owned(),
+                    ..cmd.clone()
+                };
+                nested.as_handler().handle(&cmd, params, responder)
+            },
+        );
+        assert_eq!(
+            handle(&parent, &cmd("staging deploy api")),
+            Some("api in staging".to_owned())
+        );
+    }
+
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn after(&self, _: &Command, _: &Params, response: Option<Response>) -> Option<Response> {
+            response.map(|mut response| {
+                response.text = response.text.map(|text| format!("{} {}", self.0, text));
+                response
+            })
+        }
+    }
+
+    struct Deny;
+
+    impl Middleware for Deny {
+        fn before(&self, cmd: &Command, _: &Params) -> Flow {
+            if cmd.text == "deny" {
+                Flow::Respond(Some(Response::ephemeral("denied")))
+            } else {
+                Flow::Continue
+            }
+        }
+    }
+
+    #[test]
+    fn runs_middleware_around_handlers() {
+        let mut mux = Mux::new();
+        mux.middleware(Tag("global"));
+        mux.command(
+            "/test",
+            "token",
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("ok")),
+        ).middleware(Deny)
+            .middleware(Tag("route"));
+        mux.fallback(DidYouMean);
+        let cmd = |token: &str, text: &str| command("/test").token(token).text(text).build();
+        assert_eq!(
+            handle(&mux, &cmd("token", "")),
+            Some("global route ok".to_owned())
+        );
+        assert_eq!(
+            handle(&mux, &cmd("token", "deny")),
+            Some("global denied".to_owned())
+        );
+        assert_eq!(handle(&mux, &cmd("other", "")), None);
+        let unknown = Command {
+            command: "/tes".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(
+            handle(&mux, &unknown),
+            Some("global Sorry, I don't understand `/tes`. Did you mean `/test`?".to_owned())
+        );
+    }
+
+    #[test]
+    fn restricts_routes_to_roles() {
+        let mut roles = rbac::StaticRoles::new();
+        roles.user(UserId::new("U1").unwrap(), vec!["oncall"]);
+        let roles = Arc::new(roles);
+        let mut mux = Mux::new();
+        mux.matching(
+            MatchSubCommand::new("deploy"),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("deployed")),
+        ).roles(roles.clone(), vec!["oncall", "admin"]);
+        mux.matching(
+            MatchSubCommand::new("status"),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("ok")),
+        );
+        let cmd = |user: &str, text: &str| command("/ops").user(user).text(text).build();
+        assert_eq!(
+            handle(&mux, &cmd("U1", "deploy api")),
+            Some("deployed".to_owned())
+        );
+        assert_eq!(
+            handle(&mux, &cmd("U2", "deploy api")),
+            Some("Sorry, you are not allowed to use `/ops deploy api`".to_owned())
+        );
+        assert_eq!(handle(&mux, &cmd("U2", "status")), Some("ok".to_owned()));
+        assert_eq!(
+            mux.routes().next().map(|route| route.info().roles.clone()),
+            Some(vec!["oncall".to_owned(), "admin".to_owned()])
+        );
+    }
+
+    #[test]
+    fn confirms_commands_before_handling() {
+        let mut child = Mux::new();
+        child
+            .matching(
+                MatchSubCommand::new("drop"),
+                |cmd: &Command, _: &Params, _: Box<Responder>| {
+                    Some(Response::ephemeral(format!("ran {}", cmd.text)))
+                },
+            )
+            .confirm("Really run `{}`?", Duration::from_secs(60));
+        child
+            .matching(
+                MatchSubCommand::new("truncate"),
+                |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("truncated")),
+            )
+            .confirm("Really run `{}`?", Duration::from_secs(0));
+        let mut mux = Mux::new();
+        mux.mount("/db", child);
+        let cmd = |text: &str| command("/db").token("token").user("U1").text(text).build();
+        let prompt = |cmd: &Command| {
+            let resp = mux.as_handler()
+                .handle(cmd, &Params::new(), Box::new(NoopResponder))
+                .unwrap();
+            assert_eq!(resp.text, Some(format!("Really run `/db {}`?", cmd.text)));
+            resp.attachments[0].callback_id.clone().unwrap()
+        };
+        let click_with = |token: &str, id: &str, user: &str, value: &str| {
+            let payload = ActionPayload {
+                callback_id: id.to_owned(),
+                token: token.to_owned(),
+                user: PayloadUser { id: user.to_owned() },
+                actions: vec![
+                    PayloadAction {
+                        name: value.to_owned(),
+                        value: Some(value.to_owned()),
+                    },
+                ],
+                ..Default::default()
+            };
+            confirm::interact(&mux, &payload, &RequestInfo::default()).and_then(|res| res.text)
+        };
+        let click = |id: &str, user: &str, value: &str| click_with("token", id, user, value);
+        let id = prompt(&cmd("drop users"));
+        assert_eq!(click_with("other", &id, "U1", "confirm"), None);
+        assert_eq!(
+            click(&id, "U2", "confirm"),
+            Some("Only the user who issued `/db drop users` may confirm it".to_owned())
+        );
+        assert_eq!(
+            click(&id, "U1", "confirm"),
+            Some("ran drop users".to_owned())
+        );
+        assert_eq!(
+            click(&id, "U1", "confirm"),
+            Some("This confirmation is no longer pending".to_owned())
+        );
+        let id = prompt(&cmd("drop users"));
+        assert_eq!(
+            click(&id, "U1", "cancel"),
+            Some("Cancelled `/db drop users`".to_owned())
+        );
+        let id = prompt(&cmd("truncate users"));
+        assert_eq!(
+            click(&id, "U1", "confirm"),
+            Some("Confirmation of `/db truncate users` timed out".to_owned())
+        );
+    }
+
+    #[test]
+    fn answers_action_payloads() {
+        let mut mux = Mux::new();
+        mux.command(
+            "/db",
+            "token",
+            |cmd: &Command, _: &Params, _: Box<Responder>| {
+                Some(Response::ephemeral(format!("ran {}", cmd.text)))
+            },
+        ).confirm("Really run `{}`?", Duration::from_secs(60));
+        mux.actions(|payload: &ActionPayload, _: &RequestInfo| {
+            Some(Response::ephemeral(format!("clicked {}", payload.callback_id)))
+        });
+        let mut listening = Server::http("127.0.0.1:0").unwrap().handle(mux).unwrap();
+        let url = format!("http://{}", listening.socket);
+        let post = |pairs: &[(&str, &str)]| {
+            let body = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish();
+            let mut res = Client::new()
+                .post(&url)
+                .header(ContentType::form_url_encoded())
+                .body(&body[..])
+                .send()
+                .unwrap();
+            let mut body = String::new();
+            let _ = res.read_to_string(&mut body);
+            (res.status, body)
+        };
+        let click = |id: &str| {
+            format!(
+                r#"{{"callback_id":"{}","token":"token","response_url":"http://localhost/",
+                    "user":{{"id":"U1"}},"actions":[{{"name":"confirm","value":"confirm"}}]}}"#,
+                id
+            )
+        };
+        let (_, prompt) = post(
+            &[
+                ("token", "token"),
+                ("team_id", "T1"),
+                ("channel_id", "C1"),
+                ("user_id", "U1"),
+                ("command", "/db"),
+                ("text", "drop users"),
+                ("response_url", "http://localhost/"),
+            ],
+        );
+        let confirmed = serde_json::from_str::<serde_json::Value>(&prompt)
+            .ok()
+            .and_then(|prompt| {
+                prompt
+                    .pointer("/attachments/0/callback_id")
+                    .and_then(|id| id.as_str())
+                    .map(|id| post(&[("payload", &click(id))]))
+            });
+        let clicked = post(&[("payload", &click("menu"))]);
+        let invalid = post(&[("payload", "{")]);
+        let _ = listening.close();
+        assert_eq!(
+            confirmed.map(|(_, body)| body),
+            Some(r#"{"text":"ran drop users","response_type":"ephemeral"}"#.to_owned())
+        );
+        assert_eq!(
+            clicked.1,
+            r#"{"text":"clicked menu","response_type":"ephemeral"}"#
+        );
+        assert_eq!(invalid.0, StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn provides_context_to_handlers() {
+        struct Greeting(&'static str);
+        let mut child = Mux::new();
+        child.state(Greeting("howdy"));
+        child.matching(
+            MatchSubCommand::new("greet"),
+            with_context(|ctx: Context| {
+                Some(Response::ephemeral(format!(
+                    "{} {} ({}, {})",
+                    ctx.state::<Greeting>().map(|g| g.0).unwrap_or_default(),
+                    ctx.command.text,
+                    ctx.state::<u32>().cloned().unwrap_or_default(),
+                    ctx.request.id
+                )))
+            }),
+        );
+        let mut mux = Mux::new();
+        mux.state(Greeting("hello"));
+        mux.state(42u32);
+        mux.mount("/test", child);
+        mux.matching(
+            MatchCommand::new("/legacy"),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("ok")),
+        );
+        let mut headers = ::hyper::header::Headers::new();
+        headers.set_raw("X-Request-Id", vec![b"req-1".to_vec()]);
+        let request = RequestInfo::new(None, headers);
+        let cmd = Command {
+            command: "/test".to_owned(),
+            text: "greet world".to_owned(),
+            ..Default::default()
+        };
+        let params = Params::new();
+        let ctx = Context::new(&cmd, &params, Box::new(NoopResponder), &request);
+        assert_eq!(
+            mux.as_handler().handle_context(ctx).and_then(|res| res.text),
+            Some("howdy greet world (42, req-1)".to_owned())
+        );
+        let legacy = Command {
+            command: "/legacy".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(handle(&mux, &legacy), Some("ok".to_owned()));
+    }
+
+    #[test]
+    fn verifies_tokens_before_middleware() {
+        let mut child = Mux::new();
+        child.command(
+            "/ops",
+            "token",
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("deployed")),
+        );
+        let mut mux = Mux::new();
+        mux.middleware(RateLimit::new(
+            RateLimitKey::User,
+            1,
+            ::std::time::Duration::from_secs(60),
+        ));
+        mux.command(
+            "/test",
+            "token",
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("ok")),
+        );
+        mux.mount("/ops deploy", child);
+        let cmd = |name: &str, token: &str, user: &str| {
+            command(name).token(token).user(user).text("deploy api").build()
+        };
+        assert_eq!(handle(&mux, &cmd("/test", "forged", "U1")), None);
+        assert_eq!(
+            handle(&mux, &cmd("/test", "token", "U1")),
+            Some("ok".to_owned())
+        );
+        assert_eq!(handle(&mux, &cmd("/ops", "forged", "U2")), None);
+        assert_eq!(
+            handle(&mux, &cmd("/ops", "token", "U2")),
+            Some("deployed".to_owned())
+        );
+    }
+
+    #[test]
+    fn prefers_routes_by_priority() {
+        let mut mux = Mux::new();
+        mux.matching(
+            MatchText::new(Regex::new(r"^deploy").unwrap()),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("text")),
+        );
+        mux.matching(
+            MatchCommand::new("/ops"),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("command")),
+        );
+        let cmd = Command {
+            command: "/ops".to_owned(),
+            text: "deploy api".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(handle(&mux, &cmd), Some("text".to_owned()));
+        mux.most_specific();
+        assert_eq!(handle(&mux, &cmd), Some("command".to_owned()));
+        mux.matching(
+            MatchCommand::new("/ops").and(MatchSubCommand::new("deploy")),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("subcommand")),
+        );
+        assert_eq!(handle(&mux, &cmd), Some("subcommand".to_owned()));
+        mux.matching(
+            MatchText::new(Regex::new(r"api").unwrap()),
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("priority")),
+        ).priority(1);
+        assert_eq!(handle(&mux, &cmd), Some("priority".to_owned()));
+    }
+
+    #[test]
+    fn detects_unreachable_routes() {
+        let mut mux = Mux::new();
+        mux.matching(
+            MatchText::new(Regex::new(r".*").unwrap()),
+            |_: &Command, _: &Params, _: Box<Responder>| None,
+        );
+        mux.matching(
+            MatchCommand::new("/ops"),
+            |_: &Command, _: &Params, _: Box<Responder>| None,
+        ).priority(1);
+        assert!(mux.validate().is_ok());
+        mux.matching(
+            MatchCommand::new("/deploy"),
+            |_: &Command, _: &Params, _: Box<Responder>| None,
+        );
+        assert!(mux.validate().is_err());
+        mux.most_specific();
+        assert!(mux.validate().is_ok());
+
+        let mut mux = Mux::new();
+        mux.matching(
+            MatchCommand::new("/ops"),
+            |_: &Command, _: &Params, _: Box<Responder>| None,
+        );
+        assert!(mux.validate().is_ok());
+        mux.matching(
+            MatchText::new(Regex::new(r".*").unwrap()),
+            |_: &Command, _: &Params, _: Box<Responder>| None,
+        ).priority(1);
+        match mux.validate().as_ref().map_err(|err| err.kind()) {
+            Err(&ErrorKind::UnreachableRoute(ref route, ref catchall)) => {
+                assert_eq!(route, &vec!["/ops".to_owned()]);
+                assert!(catchall.is_empty());
+            }
+            _ => panic!("expected an unreachable route"),
+        }
+    }
+
+    #[test]
+    fn detects_matchers_which_always_match() {
+        let text = |pattern: &str| MatchText::new(Regex::new(pattern).unwrap());
+        assert!(text(".*").always_matches());
+        assert!(text("^").always_matches());
+        assert!(text(r"(?P<anything>\S*)").always_matches());
+        assert!(!text(r"^$").always_matches());
+        assert!(!text(r"\B").always_matches());
+        assert!(!text(r"^deploy").always_matches());
+        assert!(text(".*").or(MatchCommand::new("/ops")).always_matches());
+        assert!(!text(".*").and(MatchCommand::new("/ops")).always_matches());
+        assert!(!MatchCommand::new("/ops").always_matches());
+    }
+
+    #[test]
+    fn routes_commands_per_team() {
+        let mut mux = Mux::new();
+        mux.team_command(
+            "/deploy",
+            TeamId::new("T1").unwrap(),
+            "token1",
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("one")),
+        );
+        mux.team_command(
+            "/deploy",
+            EnterpriseId::new("E1").unwrap(),
+            "token2",
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("two")),
+        );
+        let cmd = Command {
+            command: "/deploy".to_owned(),
+            team_id: TeamId::new("T1").unwrap(),
+            token: "token1".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(handle(&mux, &cmd), Some("one".to_owned()));
+        let cmd = Command {
+            team_id: TeamId::new("T2").unwrap(),
+            enterprise_id: Some(EnterpriseId::new("E1").unwrap()),
+            token: "token2".to_owned(),
+            ..cmd
+        };
+        assert_eq!(handle(&mux, &cmd), Some("two".to_owned()));
+        let cmd = Command {
+            enterprise_id: None,
+            ..cmd
+        };
+        assert_eq!(handle(&mux, &cmd), None);
+    }
+
+    #[test]
+    fn looks_up_tokens_per_team() {
+        let mut tokens = HashMap::new();
+        tokens.insert("T1".parse::<Workspace>().unwrap(), "token1".to_owned());
+        let mut mux = Mux::new();
+        mux.command_with(
+            "/deploy",
+            tokens,
+            |_: &Command, _: &Params, _: Box<Responder>| Some(Response::ephemeral("ok")),
+        );
+        let cmd = Command {
+            command: "/deploy".to_owned(),
+            team_id: TeamId::new("T1").unwrap(),
+            token: "token1".to_owned(),
+            ..Default::default()
+        };
+        assert!(mux.handler(&cmd).is_some());
+        assert_eq!(handle(&mux, &cmd), Some("ok".to_owned()));
+        let wrong_token = Command {
+            token: "token2".to_owned(),
+            ..cmd.clone()
+        };
+        assert_eq!(handle(&mux, &wrong_token), None);
+        let (_, handler) = mux.handler(&wrong_token).unwrap();
+        assert_eq!(
+            handler.handle(&wrong_token, &Params::new(), Box::new(NoopResponder)),
+            None
+        );
+        let disabled = Command {
+            team_id: TeamId::new("T2").unwrap(),
+            ..cmd
+        };
+        assert!(mux.handler(&disabled).is_none());
+    }
+
     #[test]
     fn extracts_commands() {
         let mut params = HashMap::new();
         params.insert("token".to_owned(), "test_token".to_owned());
-        params.insert("team_id".to_owned(), "test_team".to_owned());
+        params.insert("team_id".to_owned(), "T0TEST".to_owned());
         params.insert("team_domain".to_owned(), "test_team_domain".to_owned());
-        params.insert("channel_id".to_owned(), "test_channel_id".to_owned());
+        params.insert("channel_id".to_owned(), "C0TEST".to_owned());
         params.insert("channel_name".to_owned(), "test_channel_name".to_owned());
-        params.insert("user_id".to_owned(), "test_user_id".to_owned());
+        params.insert("user_id".to_owned(), "U0TEST".to_owned());
         params.insert("user_name".to_owned(), "test_user_name".to_owned());
-        params.insert("command".to_owned(), "test_command".to_owned());
+        params.insert("command".to_owned(), "/test_command".to_owned());
         params.insert("text".to_owned(), "test_text".to_owned());
-        params.insert("response_url".to_owned(), "test_response_url".to_owned());
+        params.insert(
+            "response_url".to_owned(),
+            "https://hooks.slack.com/commands/test".to_owned(),
+        );
         match Command::from_params(params) {
-            Some(cmd) => {
+            Ok(cmd) => {
                 assert_eq!(
                     cmd,
                     Command {
                         token: "test_token".to_owned(),
-                        team_id: "test_team".to_owned(),
+                        team_id: TeamId::new("T0TEST").unwrap(),
                         team_domain: "test_team_domain".to_owned(),
-                        channel_id: "test_channel_id".to_owned(),
+                        enterprise_id: None,
+                        enterprise_name: None,
+                        channel_id: ChannelId::new("C0TEST").unwrap(),
                         channel_name: "test_channel_name".to_owned(),
-                        user_id: "test_user_id".to_owned(),
+                        user_id: UserId::new("U0TEST").unwrap(),
                         user_name: "test_user_name".to_owned(),
-                        command: "test_command".to_owned(),
+                        command: "/test_command".to_owned(),
                         text: "test_text".to_owned(),
-                        response_url: "test_response_url".to_owned(),
+                        response_url: "https://hooks.slack.com/commands/test".to_owned(),
+                        api_app_id: None,
+                        trigger_id: None,
+                        is_enterprise_install: false,
+                        ssl_check: false,
+                        extra: HashMap::new(),
                     }
                 )
             }
             _ => assert!(false, "failed to extract command"),
         }
     }
+
+    #[test]
+    fn extracts_optional_and_extra_fields() {
+        let mut params = HashMap::new();
+        for name in &[
+            "token",
+            "team_id",
+            "team_domain",
+            "channel_id",
+            "channel_name",
+            "user_id",
+            "user_name",
+            "command",
+            "text",
+            "response_url",
+        ]
+        {
+            params.insert(name.to_string(), "test".to_owned());
+        }
+        params.insert("team_id".to_owned(), "T0TEST".to_owned());
+        params.insert("channel_id".to_owned(), "C0TEST".to_owned());
+        params.insert("user_id".to_owned(), "U0TEST".to_owned());
+        params.insert("command".to_owned(), "/test".to_owned());
+        params.insert(
+            "response_url".to_owned(),
+            "https://hooks.slack.com/commands/test".to_owned(),
+        );
+        params.insert("enterprise_id".to_owned(), "E123".to_owned());
+        params.insert("enterprise_name".to_owned(), "".to_owned());
+        params.insert("api_app_id".to_owned(), "A123".to_owned());
+        params.insert("trigger_id".to_owned(), "123.456".to_owned());
+        params.insert("is_enterprise_install".to_owned(), "true".to_owned());
+        params.insert("future_field".to_owned(), "value".to_owned());
+        match Command::from_params(params) {
+            Ok(cmd) => {
+                assert_eq!(cmd.enterprise_id, Some(EnterpriseId::new("E123").unwrap()));
+                assert_eq!(cmd.enterprise_name, None);
+                assert_eq!(cmd.api_app_id, Some("A123".to_owned()));
+                assert_eq!(cmd.trigger_id, Some("123.456".to_owned()));
+                assert!(cmd.is_enterprise_install);
+                assert!(!cmd.ssl_check);
+                assert_eq!(cmd.extra.len(), 1);
+                assert_eq!(cmd.extra.get("future_field"), Some(&"value".to_owned()));
+            }
+            _ => assert!(false, "failed to extract command"),
+        }
+    }
+
+    #[test]
+    fn reports_missing_and_invalid_fields() {
+        let mut params = HashMap::new();
+        params.insert("token".to_owned(), "test_token".to_owned());
+        params.insert("team_id".to_owned(), "".to_owned());
+        params.insert("channel_id".to_owned(), "test_channel_id".to_owned());
+        params.insert("enterprise_id".to_owned(), "T0TEST".to_owned());
+        params.insert("command".to_owned(), "test_command".to_owned());
+        params.insert("response_url".to_owned(), "not a url".to_owned());
+        match Command::from_params(params) {
+            Err(errors::Error(ErrorKind::InvalidCommand(missing, invalid), _)) => {
+                assert_eq!(missing, vec!["team_id", "user_id"]);
+                assert_eq!(
+                    invalid,
+                    vec!["enterprise_id", "channel_id", "command", "response_url"]
+                );
+            }
+            other => assert!(false, "expected invalid command but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn leniently_extracts_optional_in_practice_fields() {
+        let mut params = HashMap::new();
+        params.insert("token".to_owned(), "test_token".to_owned());
+        params.insert("team_id".to_owned(), "T0TEST".to_owned());
+        params.insert("channel_id".to_owned(), "D0TEST".to_owned());
+        params.insert("user_id".to_owned(), "U0TEST".to_owned());
+        params.insert("command".to_owned(), "/test_command".to_owned());
+        params.insert("text".to_owned(), "".to_owned());
+        params.insert(
+            "response_url".to_owned(),
+            "https://hooks.slack.com/commands/test".to_owned(),
+        );
+        match Command::from_params(params) {
+            Ok(cmd) => {
+                assert_eq!(cmd.channel_id.kind(), ChannelKind::Direct);
+                assert_eq!(cmd.channel_name, "");
+                assert_eq!(cmd.text, "");
+            }
+            other => assert!(false, "failed to extract command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn normalizes_leading_whitespace() {
+        assert_eq!(normalize(" \u{a0}\u{200B}\u{FEFF}\tdeploy api ".to_owned()), "deploy api ");
+        assert_eq!(normalize("deploy".to_owned()), "deploy");
+        assert_eq!(normalize("\u{3000}\u{200B}".to_owned()), "");
+    }
+
+    #[test]
+    fn recognizes_ssl_checks() {
+        let mut params = HashMap::new();
+        params.insert("token".to_owned(), "test_token".to_owned());
+        let mut mux = Mux::new();
+        assert_eq!(mux.ssl_check(&params), None);
+        params.insert("ssl_check".to_owned(), "1".to_owned());
+        assert_eq!(mux.ssl_check(&params), Some(true));
+        params.insert("ssl_check".to_owned(), "true".to_owned());
+        assert_eq!(mux.ssl_check(&params), Some(true));
+        params.insert("ssl_check".to_owned(), "0".to_owned());
+        assert_eq!(mux.ssl_check(&params), None);
+        params.insert("ssl_check".to_owned(), "1".to_owned());
+        mux.ssl_check_token("other_token");
+        assert_eq!(mux.ssl_check(&params), Some(false));
+        mux.ssl_check_token("test_token");
+        assert_eq!(mux.ssl_check(&params), Some(true));
+    }
 }
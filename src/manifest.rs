@@ -0,0 +1,538 @@
+//! Generates a Slack [app manifest](https://api.slack.com/reference/manifests)
+//! from the commands a Mux serves
+
+use super::Mux;
+use api::{ApiResponder, Method};
+use errors::Result;
+use serde_json::{self, Value};
+use std::collections::BTreeSet;
+
+#[derive(Serialize)]
+struct Document {
+    display_information: DisplayInformation,
+    features: Features,
+    oauth_config: OAuthConfig,
+    settings: Settings,
+}
+
+#[derive(Serialize)]
+struct DisplayInformation {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Features {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bot_user: Option<BotUser>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    slash_commands: Vec<SlashCommand>,
+}
+
+#[derive(Serialize)]
+struct BotUser {
+    display_name: String,
+    always_online: bool,
+}
+
+/// A slash command entry
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlashCommand {
+    pub command: String,
+    pub url: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_hint: Option<String>,
+    pub should_escape: bool,
+}
+
+#[derive(Serialize)]
+struct OAuthConfig {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    redirect_urls: Vec<String>,
+    scopes: Scopes,
+}
+
+#[derive(Serialize)]
+struct Scopes {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bot: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    user: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interactivity: Option<Interactivity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_subscriptions: Option<EventSubscriptions>,
+}
+
+#[derive(Serialize)]
+struct Interactivity {
+    is_enabled: bool,
+    request_url: String,
+}
+
+#[derive(Serialize)]
+struct EventSubscriptions {
+    request_url: String,
+    bot_events: Vec<String>,
+}
+
+/// A builder for an app manifest. Slash commands are derived from a Mux's
+/// routes and bot scopes from the commands and Web API methods the app uses
+pub struct Manifest {
+    name: String,
+    description: Option<String>,
+    bot_user: Option<String>,
+    url: String,
+    commands: Vec<SlashCommand>,
+    described: BTreeSet<String>,
+    scopes: BTreeSet<String>,
+    user_scopes: BTreeSet<String>,
+    redirect_urls: Vec<String>,
+    interactivity_url: Option<String>,
+    event_url: Option<String>,
+    bot_events: Vec<String>,
+}
+
+impl Manifest {
+    /// Creates a manifest for an app with the provided name whose
+    /// commands are served at the provided url
+    pub fn new<N, U>(name: N, url: U) -> Manifest
+    where
+        N: Into<String>,
+        U: Into<String>,
+    {
+        Manifest {
+            name: name.into(),
+            description: None,
+            bot_user: None,
+            url: url.into(),
+            commands: vec![],
+            described: BTreeSet::new(),
+            scopes: BTreeSet::new(),
+            user_scopes: BTreeSet::new(),
+            redirect_urls: vec![],
+            interactivity_url: None,
+            event_url: None,
+            bot_events: vec![],
+        }
+    }
+
+    /// Sets the app's description
+    pub fn description<D>(&mut self, description: D) -> &mut Manifest
+    where
+        D: Into<String>,
+    {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Adds a bot user with the provided display name
+    pub fn bot_user<N>(&mut self, display_name: N) -> &mut Manifest
+    where
+        N: Into<String>,
+    {
+        self.bot_user = Some(display_name.into());
+        self
+    }
+
+    /// Adds a slash command entry for each command a Mux's routes match.
+    /// Descriptions and usage hints are taken from the first route describing
+    /// a command, or from routes matching its subcommands when none do. Commands
+    /// default to their own name as a description. Interactivity is enabled at
+    /// the commands url when the Mux answers interactive payloads
+    pub fn commands(&mut self, mux: &Mux) -> &mut Manifest {
+        for route in mux.routes() {
+            let info = route.info();
+            let names = route.commands();
+            for name in names.iter() {
+                let mut words = name.splitn(2, ' ');
+                let command = match words.next() {
+                    Some(command) if command.starts_with('/') => command.to_owned(),
+                    _ => continue,
+                };
+                let subcommand = words.next().unwrap_or_default().trim();
+                let described = subcommand.is_empty();
+                if !described && names.contains(&command) {
+                    // the route describes the command itself
+                    continue;
+                }
+                let idx = match self.commands.iter().position(|entry| entry.command == command) {
+                    Some(idx) => idx,
+                    _ => {
+                        self.commands.push(SlashCommand {
+                            description: command.clone(),
+                            usage_hint: None,
+                            command: command.clone(),
+                            url: self.url.clone(),
+                            should_escape: false,
+                        });
+                        self.commands.len() - 1
+                    }
+                };
+                let entry = &mut self.commands[idx];
+                entry.should_escape = entry.should_escape || info.escape;
+                if !described && self.described.contains(&command) {
+                    continue;
+                }
+                // the first route describing a command replaces
+                // anything taken from subcommand routes
+                let first = described && self.described.insert(command);
+                let usage = if described {
+                    info.usage.clone()
+                } else {
+                    Some(match info.usage {
+                        Some(ref usage) => format!("{} {}", subcommand, usage),
+                        _ => subcommand.to_owned(),
+                    })
+                };
+                if let Some(ref description) = info.description {
+                    if first || entry.description == entry.command {
+                        entry.description = description.clone();
+                    }
+                }
+                if usage.is_some() && (first || entry.usage_hint.is_none()) {
+                    entry.usage_hint = usage;
+                }
+            }
+        }
+        if !self.commands.is_empty() {
+            self.scopes.insert("commands".to_owned());
+        }
+        if mux.interactive() && self.interactivity_url.is_none() {
+            self.interactivity_url = Some(self.url.clone());
+        }
+        self
+    }
+
+    /// Requests the bot scopes required to reply with an `api::ApiResponder`
+    pub fn api_responder(&mut self) -> &mut Manifest {
+        for method in ApiResponder::methods() {
+            self.uses(*method);
+        }
+        self
+    }
+
+    /// Requests the bot scopes required to call a Web API method
+    pub fn uses(&mut self, method: Method) -> &mut Manifest {
+        self.scopes.extend(
+            method.scopes().iter().map(|scope| (*scope).to_owned()),
+        );
+        self
+    }
+
+    /// Requests additional bot scopes
+    pub fn scopes<T>(&mut self, scopes: &[T]) -> &mut Manifest
+    where
+        T: AsRef<str>,
+    {
+        self.scopes.extend(
+            scopes.iter().map(|scope| scope.as_ref().to_owned()),
+        );
+        self
+    }
+
+    /// Requests user scopes
+    pub fn user_scopes<T>(&mut self, scopes: &[T]) -> &mut Manifest
+    where
+        T: AsRef<str>,
+    {
+        self.user_scopes.extend(
+            scopes.iter().map(|scope| scope.as_ref().to_owned()),
+        );
+        self
+    }
+
+    /// Adds an oauth redirect url
+    pub fn redirect_url<U>(&mut self, url: U) -> &mut Manifest
+    where
+        U: Into<String>,
+    {
+        self.redirect_urls.push(url.into());
+        self
+    }
+
+    /// Enables interactivity, delivering interactive payloads to the provided url
+    pub fn interactivity<U>(&mut self, url: U) -> &mut Manifest
+    where
+        U: Into<String>,
+    {
+        self.interactivity_url = Some(url.into());
+        self
+    }
+
+    /// Subscribes to bot events, delivering them to the provided url
+    pub fn events<U, T>(&mut self, url: U, events: &[T]) -> &mut Manifest
+    where
+        U: Into<String>,
+        T: AsRef<str>,
+    {
+        self.event_url = Some(url.into());
+        self.bot_events = events.iter().map(|event| event.as_ref().to_owned()).collect();
+        self
+    }
+
+    /// returns the slash command entries added so far
+    pub fn slash_commands(&self) -> &[SlashCommand] {
+        &self.commands
+    }
+
+    fn document(&self) -> Document {
+        Document {
+            display_information: DisplayInformation {
+                name: self.name.clone(),
+                description: self.description.clone(),
+            },
+            features: Features {
+                bot_user: self.bot_user.as_ref().map(|name| {
+                    BotUser {
+                        display_name: name.clone(),
+                        always_online: false,
+                    }
+                }),
+                slash_commands: self.commands.clone(),
+            },
+            oauth_config: OAuthConfig {
+                redirect_urls: self.redirect_urls.clone(),
+                scopes: Scopes {
+                    bot: self.scopes.iter().cloned().collect(),
+                    user: self.user_scopes.iter().cloned().collect(),
+                },
+            },
+            settings: Settings {
+                interactivity: self.interactivity_url.as_ref().map(|url| {
+                    Interactivity {
+                        is_enabled: true,
+                        request_url: url.clone(),
+                    }
+                }),
+                event_subscriptions: self.event_url.as_ref().map(|url| {
+                    EventSubscriptions {
+                        request_url: url.clone(),
+                        bot_events: self.bot_events.clone(),
+                    }
+                }),
+            },
+        }
+    }
+
+    /// Renders the manifest as json
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.document())?)
+    }
+
+    /// Renders the manifest as yaml
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut yaml = String::new();
+        write_yaml(&serde_json::to_value(self.document())?, 0, &mut yaml)?;
+        Ok(yaml)
+    }
+}
+
+/// returns the inline yaml representation of scalars and empty collections.
+/// Strings are double quoted, which yaml reads the same as json strings
+fn inline(value: &Value) -> Result<Option<String>> {
+    Ok(match *value {
+        Value::Array(ref values) if values.is_empty() => Some("[]".to_owned()),
+        Value::Object(ref values) if values.is_empty() => Some("{}".to_owned()),
+        Value::Array(_) | Value::Object(_) => None,
+        ref scalar => Some(serde_json::to_string(scalar)?),
+    })
+}
+
+fn write_yaml(value: &Value, indent: usize, yaml: &mut String) -> Result<()> {
+    let pad = " ".repeat(indent);
+    match *value {
+        Value::Object(ref values) => {
+            for (key, value) in values.iter() {
+                match inline(value)? {
+                    Some(scalar) => yaml.push_str(&format!("{}{}: {}\n", pad, key, scalar)),
+                    _ => {
+                        yaml.push_str(&format!("{}{}:\n", pad, key));
+                        write_yaml(value, indent + 2, yaml)?;
+                    }
+                }
+            }
+        }
+        Value::Array(ref values) => {
+            for value in values {
+                match inline(value)? {
+                    Some(scalar) => yaml.push_str(&format!("{}- {}\n", pad, scalar)),
+                    _ => {
+                        // render nested collections one level deeper then
+                        // replace the leading indentation with the item marker
+                        let mut item = String::new();
+                        write_yaml(value, indent + 2, &mut item)?;
+                        yaml.push_str(&format!("{}- {}", pad, &item[indent + 2..]));
+                    }
+                }
+            }
+        }
+        ref scalar => {
+            if let Some(scalar) = inline(scalar)? {
+                yaml.push_str(&format!("{}{}\n", pad, scalar));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{MatchCommand, MatchSubCommand, MatcherExt, SubCommands};
+    use std::time::Duration;
+    use testing::ok;
+
+    fn manifest() -> Manifest {
+        let mut mux = Mux::new();
+        let mut ops = SubCommands::new();
+        ops.command("deploy", "<service>", "deploys a service", ok);
+        mux.command("/ops", "token", ops)
+            .description("operates services")
+            .usage("deploy <service>")
+            .escape();
+        mux.matching(
+            MatchCommand::new("/weather").and(MatchSubCommand::new("forecast")),
+            ok,
+        ).description("forecasts the weather");
+        let mut manifest = Manifest::new("commodore", "https://example.com/commands");
+        manifest
+            .commands(&mux)
+            .uses(Method::ChatPostMessage)
+            .uses(Method::ChatPostEphemeral)
+            .uses(Method::UsersInfo)
+            .interactivity("https://example.com/interactive");
+        manifest
+    }
+
+    #[test]
+    fn derives_slash_commands() {
+        assert_eq!(
+            manifest().slash_commands(),
+            &[
+                SlashCommand {
+                    command: "/ops".to_owned(),
+                    url: "https://example.com/commands".to_owned(),
+                    description: "operates services".to_owned(),
+                    usage_hint: Some("deploy <service>".to_owned()),
+                    should_escape: true,
+                },
+                SlashCommand {
+                    command: "/weather".to_owned(),
+                    url: "https://example.com/commands".to_owned(),
+                    description: "forecasts the weather".to_owned(),
+                    usage_hint: Some("forecast".to_owned()),
+                    should_escape: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn prefers_routes_describing_commands() {
+        let mut mux = Mux::new();
+        mux.matching(
+            MatchCommand::new("/db").and(MatchSubCommand::new("drop")),
+            ok,
+        ).description("drops tables")
+            .usage("<table>")
+            .confirm("Really run `{}`?", Duration::from_secs(60));
+        mux.matching(MatchCommand::new("/db"), ok)
+            .description("manages databases");
+        let mut manifest = Manifest::new("commodore", "https://example.com/commands");
+        manifest.commands(&mux).api_responder();
+        assert_eq!(
+            manifest.slash_commands(),
+            &[
+                SlashCommand {
+                    command: "/db".to_owned(),
+                    url: "https://example.com/commands".to_owned(),
+                    description: "manages databases".to_owned(),
+                    usage_hint: Some("drop <table>".to_owned()),
+                    should_escape: false,
+                },
+            ]
+        );
+        let json = serde_json::from_str::<Value>(&manifest.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json.pointer("/settings/interactivity/request_url"),
+            Some(&Value::String("https://example.com/commands".to_owned()))
+        );
+        assert_eq!(
+            json.pointer("/oauth_config/scopes/bot"),
+            Some(&Value::Array(vec![
+                Value::String("chat:write".to_owned()),
+                Value::String("commands".to_owned()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn renders_yaml() {
+        assert_eq!(
+            manifest().to_yaml().unwrap(),
+            r#"display_information:
+  name: "commodore"
+features:
+  slash_commands:
+    - command: "/ops"
+      description: "operates services"
+      should_escape: true
+      url: "https://example.com/commands"
+      usage_hint: "deploy <service>"
+    - command: "/weather"
+      description: "forecasts the weather"
+      should_escape: false
+      url: "https://example.com/commands"
+      usage_hint: "forecast"
+oauth_config:
+  scopes:
+    bot:
+      - "chat:write"
+      - "commands"
+      - "users:read"
+settings:
+  interactivity:
+    is_enabled: true
+    request_url: "https://example.com/interactive"
+"#
+        );
+    }
+
+    #[test]
+    fn requests_every_conversation_scope() {
+        let mut manifest = Manifest::new("commodore", "https://example.com/commands");
+        manifest.uses(Method::ConversationsInfo).uses(Method::OAuthV2Access);
+        let json = serde_json::from_str::<Value>(&manifest.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json.pointer("/oauth_config/scopes/bot"),
+            Some(&Value::Array(
+                vec!["channels:read", "groups:read", "im:read", "mpim:read"]
+                    .into_iter()
+                    .map(|scope| Value::String(scope.to_owned()))
+                    .collect(),
+            ))
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        let json = manifest().to_json().unwrap();
+        let value = serde_json::from_str::<Value>(&json).unwrap();
+        assert_eq!(
+            value.pointer("/features/slash_commands/0/command"),
+            Some(&Value::String("/ops".to_owned()))
+        );
+        assert_eq!(
+            value.pointer("/oauth_config/scopes/bot/1"),
+            Some(&Value::String("commands".to_owned()))
+        );
+        assert_eq!(value.pointer("/settings/event_subscriptions"), None);
+    }
+}
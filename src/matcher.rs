@@ -0,0 +1,281 @@
+//! Combinators for composing matchers and matchers for the context a command was issued in
+
+use super::{Captures, Command, Matcher};
+use ids::{ChannelId, ChannelKind, TeamId, UserId};
+use std::collections::HashSet;
+
+/// combines the names of matchers which must both match, i.e. `/ops` and `deploy`
+fn join(left: Vec<String>, right: Vec<String>) -> Vec<String> {
+    if left.is_empty() {
+        return right;
+    }
+    if right.is_empty() {
+        return left;
+    }
+    let mut names = vec![];
+    for l in left.iter() {
+        for r in right.iter() {
+            names.push(format!("{} {}", l, r));
+        }
+    }
+    names
+}
+
+/// merges optional captures, giving precedence to the left
+fn merge<'a>(left: Option<Captures<'a>>, right: Option<Captures<'a>>) -> Option<Captures<'a>> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(left.merge(right)),
+        (left, None) => left,
+        (None, right) => right,
+    }
+}
+
+/// Matches commands matched by both matchers, merging their captures
+pub struct And<A, B>(A, B);
+
+impl<A: Matcher, B: Matcher> Matcher for And<A, B> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        match self.0.matches(cmd) {
+            (left, true) => {
+                match self.1.matches(cmd) {
+                    (right, true) => (merge(left, right), true),
+                    _ => (None, false),
+                }
+            }
+            _ => (None, false),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        join(self.0.names(), self.1.names())
+    }
+
+    fn specificity(&self) -> u32 {
+        self.0.specificity() + self.1.specificity()
+    }
+
+    fn always_matches(&self) -> bool {
+        self.0.always_matches() && self.1.always_matches()
+    }
+}
+
+/// Matches commands matched by either matcher, preferring the first.
+/// Captures are provided by the matcher which matched
+pub struct Or<A, B>(A, B);
+
+impl<A: Matcher, B: Matcher> Matcher for Or<A, B> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        match self.0.matches(cmd) {
+            (caps, true) => (caps, true),
+            _ => self.1.matches(cmd),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names = self.0.names();
+        names.extend(self.1.names());
+        names
+    }
+
+    fn specificity(&self) -> u32 {
+        self.0.specificity().min(self.1.specificity())
+    }
+
+    fn always_matches(&self) -> bool {
+        self.0.always_matches() || self.1.always_matches()
+    }
+}
+
+/// Matches commands not matched by a matcher. No captures are provided
+pub struct Not<M>(M);
+
+impl<M: Matcher> Matcher for Not<M> {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        (None, !self.0.matches(cmd).1)
+    }
+}
+
+/// Matches commands matched by at least one of a list of matchers, merging
+/// the captures of every matcher which matched in order
+pub struct Any(Vec<Box<Matcher>>);
+
+impl Matcher for Any {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        self.0.iter().fold((None, false), |(caps, matched), matcher| {
+            match matcher.matches(cmd) {
+                (more, true) => (merge(caps, more), true),
+                _ => (caps, matched),
+            }
+        })
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.iter().flat_map(|matcher| matcher.names()).collect()
+    }
+
+    fn specificity(&self) -> u32 {
+        self.0
+            .iter()
+            .map(|matcher| matcher.specificity())
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn always_matches(&self) -> bool {
+        self.0.iter().any(|matcher| matcher.always_matches())
+    }
+}
+
+/// Matches commands matched by every one of a list of matchers, merging their captures
+pub struct All(Vec<Box<Matcher>>);
+
+impl Matcher for All {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        let mut caps = None;
+        for matcher in self.0.iter() {
+            match matcher.matches(cmd) {
+                (more, true) => caps = merge(caps, more),
+                _ => return (None, false),
+            }
+        }
+        (caps, true)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.iter().fold(vec![], |names, matcher| join(names, matcher.names()))
+    }
+
+    fn specificity(&self) -> u32 {
+        self.0.iter().map(|matcher| matcher.specificity()).sum()
+    }
+
+    fn always_matches(&self) -> bool {
+        self.0.iter().all(|matcher| matcher.always_matches())
+    }
+}
+
+/// Returns a matcher which matches commands matched by any of the provided matchers
+pub fn any(matchers: Vec<Box<Matcher>>) -> Any {
+    Any(matchers)
+}
+
+/// Returns a matcher which matches commands matched by all of the provided matchers
+pub fn all(matchers: Vec<Box<Matcher>>) -> All {
+    All(matchers)
+}
+
+/// Extension methods for composing matchers
+pub trait MatcherExt: Matcher + Sized {
+    /// matches commands matched by both this and another matcher
+    fn and<M: Matcher>(self, other: M) -> And<Self, M> {
+        And(self, other)
+    }
+
+    /// matches commands matched by either this or another matcher
+    fn or<M: Matcher>(self, other: M) -> Or<Self, M> {
+        Or(self, other)
+    }
+
+    /// matches commands not matched by this matcher
+    fn not(self) -> Not<Self> {
+        Not(self)
+    }
+}
+
+impl<M: Matcher> MatcherExt for M {}
+
+macro_rules! id_matcher {
+    ($(#[$attr:meta])* $name:ident, $id:ident, $field:ident) => {
+        $(#[$attr])*
+        pub struct $name(Box<Fn(&$id) -> bool + Send + Sync>);
+
+        impl $name {
+            /// matches a single id
+            pub fn id(id: $id) -> $name {
+                $name(Box::new(move |candidate| *candidate == id))
+            }
+
+            /// matches any of a set of ids
+            pub fn any<I>(ids: I) -> $name
+            where
+                I: IntoIterator<Item = $id>,
+            {
+                let ids = ids.into_iter().collect::<HashSet<_>>();
+                $name(Box::new(move |candidate| ids.contains(candidate)))
+            }
+
+            /// matches ids satisfying a predicate
+            pub fn when<F>(predicate: F) -> $name
+            where
+                F: Fn(&$id) -> bool + Send + Sync + 'static,
+            {
+                $name(Box::new(predicate))
+            }
+        }
+
+        impl Matcher for $name {
+            fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+                if (self.0)(&cmd.$field) {
+                    (None, true)
+                } else {
+                    debug!("{} {} did not match cmd {:?}", stringify!($field), cmd.$field, cmd.command);
+                    (None, false)
+                }
+            }
+
+            fn specificity(&self) -> u32 {
+                1
+            }
+        }
+    };
+}
+
+id_matcher!(
+    /// A matcher for commands issued by given users
+    MatchUser,
+    UserId,
+    user_id
+);
+
+id_matcher!(
+    /// A matcher for commands issued in given channels
+    MatchChannel,
+    ChannelId,
+    channel_id
+);
+
+id_matcher!(
+    /// A matcher for commands issued from given teams. Use `MatchTeamCommand`
+    /// to also match teams within an enterprise
+    MatchTeam,
+    TeamId,
+    team_id
+);
+
+/// A matcher for commands issued in kinds of channels, i.e. direct messages
+pub struct MatchChannelKind(Vec<ChannelKind>);
+
+impl MatchChannelKind {
+    /// matches a single kind of channel
+    pub fn kind(kind: ChannelKind) -> MatchChannelKind {
+        MatchChannelKind(vec![kind])
+    }
+
+    /// matches any of the provided kinds of channels
+    pub fn any<I>(kinds: I) -> MatchChannelKind
+    where
+        I: IntoIterator<Item = ChannelKind>,
+    {
+        MatchChannelKind(kinds.into_iter().collect())
+    }
+}
+
+impl Matcher for MatchChannelKind {
+    fn matches<'a>(&self, cmd: &'a Command) -> (Option<Captures<'a>>, bool) {
+        (None, self.0.contains(&cmd.channel_id.kind()))
+    }
+
+    fn specificity(&self) -> u32 {
+        1
+    }
+}
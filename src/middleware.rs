@@ -0,0 +1,111 @@
+//! Middleware which runs around command handlers
+
+use super::{Command, Context, Handler, Params, TokenLookup};
+use response::Response;
+use std::sync::Arc;
+
+/// Determines whether a command continues on to its handler
+pub enum Flow {
+    /// continue on to the next middleware or handler
+    Continue,
+    /// stop handling, replying with the provided response, if any
+    Respond(Option<Response>),
+}
+
+/// Command middleware interface. Middleware installed on a Mux runs for
+/// every command it handles, including those handled by its fallback,
+/// followed by middleware installed on the matched route
+pub trait Middleware: Send + Sync {
+    /// runs before a command is handled. Returning `Flow::Respond`
+    /// skips the handler and any remaining middleware
+    fn before(&self, _: &Command, _: &Params) -> Flow {
+        Flow::Continue
+    }
+
+    /// runs after a command is handled with the handler's response, in the
+    /// reverse order of `before`. The returned response is sent in its place
+    fn after(&self, _: &Command, _: &Params, response: Option<Response>) -> Option<Response> {
+        response
+    }
+}
+
+/// Runs middleware around a handler. `after` hooks only run for
+/// middleware whose `before` hook ran
+pub fn run(middleware: &[&Middleware], ctx: Context, handler: &Handler) -> Option<Response> {
+    let (cmd, params) = (ctx.command, ctx.params);
+    let mut ran = 0;
+    let mut early = None;
+    for m in middleware {
+        ran += 1;
+        if let Flow::Respond(response) = m.before(cmd, params) {
+            early = Some(response);
+            break;
+        }
+    }
+    let mut response = match early {
+        Some(response) => response,
+        _ => handler.handle_context(ctx),
+    };
+    for m in middleware[..ran].iter().rev() {
+        response = m.after(cmd, params, response);
+    }
+    response
+}
+
+/// A middleware which only continues for commands with the provided verification token
+pub struct TokenValidator {
+    token: String,
+}
+
+impl TokenValidator {
+    pub fn new<T>(token: T) -> TokenValidator
+    where
+        T: Into<String>,
+    {
+        TokenValidator { token: token.into() }
+    }
+}
+
+impl Middleware for TokenValidator {
+    fn before(&self, cmd: &Command, _: &Params) -> Flow {
+        if cmd.token == self.token {
+            Flow::Continue
+        } else {
+            error!(
+                "cmd token ${:?} did not match handler token ${:?}",
+                cmd.token,
+                self.token
+            );
+            Flow::Respond(None)
+        }
+    }
+}
+
+/// A middleware which only continues for commands with the verification
+/// token a TokenLookup provides for the team they were issued from
+pub struct TeamTokenValidator<L: TokenLookup + 'static> {
+    lookup: Arc<L>,
+}
+
+impl<L: TokenLookup + 'static> TeamTokenValidator<L> {
+    pub fn new(lookup: Arc<L>) -> TeamTokenValidator<L> {
+        TeamTokenValidator { lookup: lookup }
+    }
+}
+
+impl<L: TokenLookup + 'static> Middleware for TeamTokenValidator<L> {
+    fn before(&self, cmd: &Command, _: &Params) -> Flow {
+        match self.lookup.token(&cmd.team_id, cmd.enterprise_id.as_ref()) {
+            Some(ref token) if *token == cmd.token => Flow::Continue,
+            token => {
+                error!(
+                    "cmd token ${:?} did not match team {:?} token ${:?}",
+                    cmd.token,
+                    cmd.team_id,
+                    token
+                );
+                Flow::Respond(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Mounting of child Muxes under command prefixes
+
+use super::{word_prefix, Command, Context, Flow, Handler, Middleware, Mux, Params, RequestInfo,
+            Responder};
+use response::Response;
+use std::sync::Arc;
+
+/// A Handler which dispatches commands to a child Mux with a prefix of
+/// subcommand words removed from their text
+pub struct Mount {
+    prefix: String,
+    words: String,
+    child: Arc<Mux>,
+}
+
+impl Mount {
+    /// creates a mount for a prefix of a command followed by optional
+    /// subcommand words, i.e. `/ops deploy`, or subcommand words alone
+    pub fn new(prefix: String, child: Mux) -> Mount {
+        let words = if prefix.starts_with('/') {
+            prefix
+                .splitn(2, char::is_whitespace)
+                .nth(1)
+                .unwrap_or_default()
+                .trim()
+                .to_owned()
+        } else {
+            prefix.clone()
+        };
+        Mount {
+            prefix: prefix,
+            words: words,
+            child: Arc::new(child),
+        }
+    }
+
+    /// returns the command of the prefix, if any
+    pub fn command(&self) -> Option<&str> {
+        if self.prefix.starts_with('/') {
+            self.prefix.split_whitespace().next()
+        } else {
+            None
+        }
+    }
+
+    /// returns the subcommand words of the prefix
+    pub fn words(&self) -> &str {
+        &self.words
+    }
+
+    /// returns the mounted Mux
+    pub fn child(&self) -> &Mux {
+        &self.child
+    }
+
+    /// returns middleware which verifies commands with the
+    /// verification of the child route they will be routed to
+    pub fn verification(&self) -> VerifyMounted {
+        VerifyMounted {
+            words: self.words.clone(),
+            child: self.child.clone(),
+        }
+    }
+}
+
+/// returns a command with a prefix of subcommand words removed from its text
+fn strip(words: &str, cmd: &Command) -> Command {
+    let text = match word_prefix(&cmd.text, words, false) {
+        Some(idx) => cmd.text[idx..].trim_left(),
+        _ => &cmd.text[..],
+    };
+    Command {
+        text: text.to_owned(),
+        ..cmd.clone()
+    }
+}
+
+/// A middleware which verifies commands routed to a mounted Mux before
+/// middleware installed on the parent Mux runs. Commands which none of the
+/// child's routes match are left to the child's help and fallback
+pub struct VerifyMounted {
+    words: String,
+    child: Arc<Mux>,
+}
+
+impl Middleware for VerifyMounted {
+    fn before(&self, cmd: &Command, params: &Params) -> Flow {
+        let child_cmd = strip(&self.words, cmd);
+        match self.child.preferred(&child_cmd) {
+            Some((_, route)) => route.verify(&child_cmd, params),
+            _ => Flow::Continue,
+        }
+    }
+}
+
+impl Handler for Mount {
+    fn handle(&self, cmd: &Command, params: &Params, responder: Box<Responder>) -> Option<Response> {
+        let request = RequestInfo::default();
+        self.handle_context(Context::new(cmd, params, responder, &request))
+    }
+
+    fn handle_context(&self, ctx: Context) -> Option<Response> {
+        let cmd = ctx.command;
+        let child_cmd = strip(&self.words, cmd);
+        let text = &child_cmd.text[..];
+        let prefix = if self.prefix.starts_with('/') {
+            self.prefix.clone()
+        } else {
+            format!("{} {}", cmd.command, self.prefix)
+        };
+        if self.child.handler(&child_cmd).is_none() {
+            if text.is_empty() || text == "help" {
+                return Some(self.child.help(&prefix));
+            }
+            if self.child.fallback.is_none() {
+                let mut help = self.child.help(&prefix);
+                help.text = help.text.map(|help| {
+                    format!("Unknown command `{} {}`\n{}", prefix, text, help)
+                });
+                return Some(help);
+            }
+        }
+        let params = ctx.params;
+        self.child.handle_context(ctx.with_command(&child_cmd, params))
+    }
+
+    fn as_handler(&self) -> &Handler {
+        self
+    }
+
+    fn subcommands(&self) -> Vec<String> {
+        self.child.names()
+    }
+}
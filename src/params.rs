@@ -0,0 +1,155 @@
+//! Owned parameters captured while matching a command
+
+use super::Captures;
+use errors::{ErrorKind, Result};
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Named and positional groups captured while matching a command.
+/// Unlike `Captures`, params own their values so they may be moved
+/// into threads which outlive the request
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Params {
+    named: HashMap<String, String>,
+    positional: Vec<Option<String>>,
+}
+
+impl Params {
+    pub fn new() -> Params {
+        Params { ..Default::default() }
+    }
+
+    /// returns the raw value of a named group
+    pub fn name(&self, name: &str) -> Option<&str> {
+        self.named.get(name).map(|value| &value[..])
+    }
+
+    /// returns the raw value of a positional group. Group `0` is the entire match
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).and_then(
+            |value| value.as_ref().map(|value| &value[..]),
+        )
+    }
+
+    /// parses the value of a named group
+    pub fn get<T>(&self, name: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.name(name) {
+            Some(value) => parse(name, value),
+            _ => Err(ErrorKind::MissingParam(name.to_owned()).into()),
+        }
+    }
+
+    /// parses the value of a positional group
+    pub fn at<T>(&self, index: usize) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let name = index.to_string();
+        match self.positional(index) {
+            Some(value) => parse(&name, value),
+            _ => Err(ErrorKind::MissingParam(name).into()),
+        }
+    }
+
+    /// sets the value of a named group
+    pub fn insert<N, V>(&mut self, name: N, value: V)
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.named.insert(name.into(), value.into());
+    }
+
+    /// adds the named groups of other params which are not already present
+    pub fn merge(&mut self, other: &Params) {
+        for (name, value) in other.iter() {
+            if !self.named.contains_key(name) {
+                self.named.insert(name.clone(), value.clone());
+            }
+        }
+    }
+
+    /// returns true when no groups were captured
+    pub fn is_empty(&self) -> bool {
+        self.named.is_empty() && self.positional.is_empty()
+    }
+
+    /// iterates over named groups
+    pub fn iter(&self) -> Iter<String, String> {
+        self.named.iter()
+    }
+}
+
+fn parse<T>(name: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|err: T::Err| {
+        ErrorKind::InvalidParam(name.to_owned(), value.to_owned(), err.to_string()).into()
+    })
+}
+
+/// Named groups take the value of the first set of captures they participated in
+/// and positional groups are taken from the first set of captures
+impl<'a, 'c> From<&'c Captures<'a>> for Params {
+    fn from(caps: &'c Captures<'a>) -> Params {
+        let mut params = Params::new();
+        for name in caps.names() {
+            if let Some(value) = caps.name(name) {
+                params.insert(name, value.as_str());
+            }
+        }
+        if let Some(first) = caps.sets().first() {
+            params.positional = first
+                .iter()
+                .map(|value| value.map(|value| value.as_str().to_owned()))
+                .collect();
+        }
+        params
+    }
+}
+
+impl<'a> From<Option<Captures<'a>>> for Params {
+    fn from(caps: Option<Captures<'a>>) -> Params {
+        caps.as_ref().map(Params::from).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn extracts_params() {
+        let deploy = Regex::new(r"^(?P<action>\S+) (?P<count>\S+)(?: (?P<env>\S+))?$").unwrap();
+        let service = Regex::new(r"(?P<action>\S+) (?P<service>\S+) \S+$").unwrap();
+        let caps = Captures::from_regex(&deploy, "scale 3")
+            .unwrap()
+            .merge(Captures::from_regex(&service, "restart api 3").unwrap());
+        let params = Params::from(&caps);
+        assert_eq!(params.name("action"), Some("scale"));
+        assert_eq!(params.name("service"), Some("api"));
+        assert_eq!(params.name("env"), None);
+        assert_eq!(params.get::<u32>("count").unwrap(), 3);
+        assert_eq!(params.at::<String>(0).unwrap(), "scale 3");
+        assert_eq!(params.positional(3), None);
+        assert_eq!(
+            params.get::<u32>("action").unwrap_err().to_string(),
+            "invalid value `scale` for param action: invalid digit found in string"
+        );
+        assert_eq!(
+            params.get::<u32>("env").unwrap_err().to_string(),
+            "missing param env"
+        );
+        assert!(Params::from(None).is_empty());
+    }
+}
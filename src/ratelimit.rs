@@ -0,0 +1,222 @@
+//! Token bucket rate limiting for commands
+
+use super::{Command, Flow, Middleware, Params};
+use response::Response;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// buckets are pruned once this many are tracked
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// What a RateLimit counts invocations by. Users and channels are
+/// counted per enterprise, or per team outside of enterprise grids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    User,
+    Channel,
+    Team,
+    Command,
+}
+
+impl RateLimitKey {
+    fn key(&self, cmd: &Command) -> String {
+        match *self {
+            RateLimitKey::User => format!("{}:{}", workspace(cmd), cmd.user_id),
+            RateLimitKey::Channel => format!("{}:{}", workspace(cmd), cmd.channel_id),
+            RateLimitKey::Team => cmd.team_id.to_string(),
+            RateLimitKey::Command => cmd.command.clone(),
+        }
+    }
+}
+
+/// returns the enterprise a command was issued in, or its team outside of enterprise grids
+fn workspace(cmd: &Command) -> &str {
+    cmd.enterprise_id.as_ref().map_or(
+        cmd.team_id.as_str(),
+        |id| id.as_str(),
+    )
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A middleware which limits how often commands may be invoked per user,
+/// channel, team, or command. Each key has a bucket holding up to `capacity`
+/// tokens which refills over `period`. Invocations take a token and are
+/// answered with an ephemeral message when none remain. A capacity of `0`
+/// denies every invocation
+pub struct RateLimit {
+    key: RateLimitKey,
+    capacity: f64,
+    period: Duration,
+    message: String,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimit {
+    /// creates a limit of `capacity` invocations per `period` for each key
+    pub fn new(key: RateLimitKey, capacity: u32, period: Duration) -> RateLimit {
+        RateLimit {
+            key: key,
+            capacity: f64::from(capacity),
+            period: period,
+            message: "Slow down! Try again in {}s".to_owned(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// sets the message limited invocations are answered with. `{}`
+    /// is replaced with the number of seconds until a token is available
+    pub fn message<M>(mut self, message: M) -> RateLimit
+    where
+        M: Into<String>,
+    {
+        self.message = message.into();
+        self
+    }
+
+    /// tokens added per second
+    fn rate(&self) -> f64 {
+        let period = self.period.as_secs() as f64 + f64::from(self.period.subsec_nanos()) / 1e9;
+        if period > 0.0 {
+            self.capacity / period
+        } else {
+            ::std::f64::INFINITY
+        }
+    }
+
+    /// takes a token for a key, returning how long to wait when none remain
+    fn take(&self, key: String, now: Instant) -> Option<Duration> {
+        let rate = self.rate();
+        let capacity = self.capacity;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + elapsed(bucket.updated, now) * rate < capacity
+            });
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens + elapsed(bucket.updated, now) * rate).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            let wait = (1.0 - bucket.tokens) / rate;
+            Some(Duration::from_millis((wait * 1000.0).ceil() as u64))
+        }
+    }
+}
+
+/// seconds between two instants, or zero when `later` is earlier
+fn elapsed(earlier: Instant, later: Instant) -> f64 {
+    if later > earlier {
+        let elapsed = later - earlier;
+        elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9
+    } else {
+        0.0
+    }
+}
+
+impl Middleware for RateLimit {
+    fn before(&self, cmd: &Command, _: &Params) -> Flow {
+        if self.capacity < 1.0 {
+            debug!("rate limit of {:?} denies every cmd", self.key);
+            return Flow::Respond(Some(Response::ephemeral(
+                format!("Sorry, `{}` is disabled", cmd.command),
+            )));
+        }
+        match self.take(self.key.key(cmd), Instant::now()) {
+            None => Flow::Continue,
+            Some(wait) => {
+                let secs = wait.as_secs() + if wait.subsec_nanos() > 0 { 1 } else { 0 };
+                debug!(
+                    "rate limited {:?} {:?} for {}s",
+                    self.key,
+                    self.key.key(cmd),
+                    secs
+                );
+                Flow::Respond(Some(Response::ephemeral(
+                    self.message.replace("{}", &secs.to_string()),
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::command;
+
+    #[test]
+    fn refills_buckets_over_time() {
+        let limit = RateLimit::new(RateLimitKey::User, 2, Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(limit.take("U1".to_owned(), start), None);
+        assert_eq!(limit.take("U1".to_owned(), start), None);
+        assert_eq!(
+            limit.take("U1".to_owned(), start),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(limit.take("U2".to_owned(), start), None);
+        assert_eq!(
+            limit.take("U1".to_owned(), start + Duration::from_secs(3)),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            limit.take("U1".to_owned(), start + Duration::from_secs(5)),
+            None
+        );
+    }
+
+    #[test]
+    fn answers_limited_commands() {
+        let limit = RateLimit::new(RateLimitKey::Command, 1, Duration::from_secs(60))
+            .message("slow down, try again in {}s");
+        let cmd = command("/deploy").build();
+        let params = Params::new();
+        assert!(match limit.before(&cmd, &params) {
+            Flow::Continue => true,
+            _ => false,
+        });
+        match limit.before(&cmd, &params) {
+            Flow::Respond(Some(response)) => {
+                assert_eq!(response.text, Some("slow down, try again in 60s".to_owned()))
+            }
+            _ => panic!("expected a response"),
+        }
+        let disabled = RateLimit::new(RateLimitKey::Command, 0, Duration::from_secs(60));
+        match disabled.before(&cmd, &params) {
+            Flow::Respond(Some(response)) => {
+                assert_eq!(response.text, Some("Sorry, `/deploy` is disabled".to_owned()))
+            }
+            _ => panic!("expected a response"),
+        }
+    }
+
+    #[test]
+    fn keys_users_and_channels_by_workspace() {
+        let cmd = |team: &str, enterprise: Option<&str>| {
+            command("/deploy")
+                .team(team)
+                .enterprise(enterprise)
+                .user("U1")
+                .channel("C1")
+                .build()
+        };
+        for key in &[RateLimitKey::User, RateLimitKey::Channel] {
+            assert!(key.key(&cmd("T1", None)) != key.key(&cmd("T2", None)));
+            assert_eq!(
+                key.key(&cmd("T1", Some("E1"))),
+                key.key(&cmd("T2", Some("E1")))
+            );
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Role based access control for commands
+//!
+//! Roles are resolved for the user issuing a command by a `RoleProvider`.
+//! `StaticRoles` assigns roles to users and user groups from a json file
+//! such as
+//!
+//! ```json
+//! {
+//!   "users": { "U024BE7LH": ["admin"] },
+//!   "usergroups": {
+//!     "S0614TZR7": { "members": ["U024BE7LH", "U0G9QF9C6"], "roles": ["oncall"] }
+//!   }
+//! }
+//! ```
+//!
+//! Members of user groups may instead be looked up from Slack with
+//! `StaticRoles::with_members` and a `UserGroupMembers` such as `api::Client`.
+//! Roles are listed in the order of the users and user groups granting them.
+//!
+//! Routes require roles with `Route::roles`. Refused invocations are answered
+//! with an ephemeral denial and logged under the `commodore::audit` target
+
+use super::{Command, Flow, Middleware, Params};
+use errors::Result;
+use ids::{UserGroupId, UserId};
+use response::Response;
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// how long looked up user group members are cached by default
+const DEFAULT_MEMBERS_TTL_SECS: u64 = 300;
+
+/// how long a failed lookup is remembered before members are looked up again
+const FAILED_LOOKUP_TTL_SECS: u64 = 30;
+
+/// Role lookup interface
+pub trait RoleProvider: Send + Sync {
+    /// returns the roles of the user who issued a command
+    fn roles(&self, cmd: &Command) -> Vec<String>;
+}
+
+impl<F> RoleProvider for F
+where
+    F: Fn(&Command) -> Vec<String>,
+    F: Send + Sync,
+{
+    fn roles(&self, cmd: &Command) -> Vec<String> {
+        self(cmd)
+    }
+}
+
+/// User group membership lookup interface
+pub trait UserGroupMembers: Send + Sync {
+    /// returns the ids of the members of a user group
+    fn members(&self, usergroup_id: &UserGroupId) -> Result<Vec<UserId>>;
+}
+
+impl<F> UserGroupMembers for F
+where
+    F: Fn(&UserGroupId) -> Result<Vec<UserId>>,
+    F: Send + Sync,
+{
+    fn members(&self, usergroup_id: &UserGroupId) -> Result<Vec<UserId>> {
+        self(usergroup_id)
+    }
+}
+
+/// Members of a user group and the roles they are granted
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct UserGroup {
+    #[serde(default)]
+    pub members: Vec<UserId>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// A RoleProvider with roles assigned to user ids and user group ids
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct StaticRoles {
+    #[serde(default)]
+    users: HashMap<UserId, Vec<String>>,
+    #[serde(default)]
+    usergroups: BTreeMap<UserGroupId, UserGroup>,
+}
+
+impl StaticRoles {
+    pub fn new() -> StaticRoles {
+        StaticRoles { ..Default::default() }
+    }
+
+    /// reads roles from a json file
+    pub fn from_file<P>(path: P) -> Result<StaticRoles>
+    where
+        P: AsRef<Path>,
+    {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        StaticRoles::from_json(&contents)
+    }
+
+    /// parses roles from json
+    pub fn from_json(json: &str) -> Result<StaticRoles> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// grants roles to a user
+    pub fn user<I, R>(&mut self, user_id: UserId, roles: I) -> &mut StaticRoles
+    where
+        I: IntoIterator<Item = R>,
+        R: Into<String>,
+    {
+        self.users
+            .entry(user_id)
+            .or_insert_with(Vec::new)
+            .extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    /// grants roles to the members of a user group
+    pub fn usergroup(&mut self, usergroup_id: UserGroupId, group: UserGroup) -> &mut StaticRoles {
+        self.usergroups.insert(usergroup_id, group);
+        self
+    }
+
+    /// returns a RoleProvider which also grants the roles of user groups
+    /// to their members as looked up from `members`
+    pub fn with_members<M>(self, members: M) -> LookupRoles<M>
+    where
+        M: UserGroupMembers,
+    {
+        LookupRoles {
+            roles: self,
+            members: members,
+            ttl: Duration::from_secs(DEFAULT_MEMBERS_TTL_SECS),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// returns the roles of a user, in user group id order after
+    /// their own, given whether they are a member of each user group
+    fn resolve<F>(&self, user: &UserId, is_member: F) -> Vec<String>
+    where
+        F: Fn(&UserGroupId, &UserGroup) -> bool,
+    {
+        let mut roles = self.users.get(user).cloned().unwrap_or_default();
+        for (id, group) in self.usergroups.iter() {
+            if is_member(id, group) {
+                roles.extend(group.roles.iter().cloned());
+            }
+        }
+        roles
+    }
+}
+
+impl RoleProvider for StaticRoles {
+    fn roles(&self, cmd: &Command) -> Vec<String> {
+        let user = &cmd.user_id;
+        self.resolve(user, |_, group| group.members.contains(user))
+    }
+}
+
+/// A RoleProvider which grants the roles of a `StaticRoles`, looking up
+/// the members of its user groups. Members are cached for five minutes by
+/// default. When a lookup fails only a user group's listed members are granted
+/// its roles, and the group is not looked up again for thirty seconds
+pub struct LookupRoles<M> {
+    roles: StaticRoles,
+    members: M,
+    ttl: Duration,
+    cache: Mutex<HashMap<UserGroupId, (Instant, Option<Vec<UserId>>)>>,
+}
+
+impl<M> LookupRoles<M>
+where
+    M: UserGroupMembers,
+{
+    /// sets how long looked up members are cached
+    pub fn ttl(mut self, ttl: Duration) -> LookupRoles<M> {
+        self.ttl = ttl;
+        self
+    }
+
+    /// returns true if a user is a member of a user group. The cache is not
+    /// locked while members are looked up, so a slow lookup only delays the
+    /// commands which need it
+    fn is_member(&self, user: &UserId, usergroup_id: &UserGroupId, group: &UserGroup) -> bool {
+        if group.members.contains(user) {
+            return true;
+        }
+        let now = Instant::now();
+        let cached = self.cache.lock().unwrap().get(usergroup_id).and_then(
+            |&(at, ref members)| {
+                let ttl = if members.is_some() {
+                    self.ttl
+                } else {
+                    Duration::from_secs(FAILED_LOOKUP_TTL_SECS)
+                };
+                if now.duration_since(at) < ttl {
+                    Some(contains(members, user))
+                } else {
+                    None
+                }
+            },
+        );
+        if let Some(member) = cached {
+            return member;
+        }
+        let members = match self.members.members(usergroup_id) {
+            Ok(members) => Some(members),
+            Err(err) => {
+                warn!("failed to look up members of {}: {}", usergroup_id, err);
+                None
+            }
+        };
+        let member = contains(&members, user);
+        self.cache.lock().unwrap().insert(
+            usergroup_id.clone(),
+            (now, members),
+        );
+        member
+    }
+}
+
+/// returns true if looked up members include a user
+fn contains(members: &Option<Vec<UserId>>, user: &UserId) -> bool {
+    members.as_ref().map_or(false, |members| members.contains(user))
+}
+
+impl<M> RoleProvider for LookupRoles<M>
+where
+    M: UserGroupMembers,
+{
+    fn roles(&self, cmd: &Command) -> Vec<String> {
+        let user = &cmd.user_id;
+        self.roles.resolve(
+            user,
+            |id, group| self.is_member(user, id, group),
+        )
+    }
+}
+
+/// A middleware which only continues for users holding at least one of a set of roles
+pub struct RequireRoles {
+    provider: Arc<RoleProvider>,
+    roles: Vec<String>,
+    message: String,
+}
+
+impl RequireRoles {
+    pub fn new<P, I, R>(provider: Arc<P>, roles: I) -> RequireRoles
+    where
+        P: RoleProvider + 'static,
+        I: IntoIterator<Item = R>,
+        R: Into<String>,
+    {
+        RequireRoles {
+            provider: provider,
+            roles: roles.into_iter().map(Into::into).collect(),
+            message: "Sorry, you are not allowed to use `{}`".to_owned(),
+        }
+    }
+
+    /// sets the message refused invocations are answered with.
+    /// `{}` is replaced with the command as it was invoked
+    pub fn message<M>(mut self, message: M) -> RequireRoles
+    where
+        M: Into<String>,
+    {
+        self.message = message.into();
+        self
+    }
+
+    /// returns the roles which are permitted
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
+}
+
+impl Middleware for RequireRoles {
+    fn before(&self, cmd: &Command, _: &Params) -> Flow {
+        let held = self.provider.roles(cmd);
+        if held.iter().any(|role| self.roles.contains(role)) {
+            return Flow::Continue;
+        }
+        let invoked = if cmd.text.is_empty() {
+            cmd.command.clone()
+        } else {
+            format!("{} {}", cmd.command, cmd.text)
+        };
+        warn!(
+            target: "commodore::audit",
+            "denied {:?} to user {} ({}) of team {} in channel {}. requires one of {:?}, holds {:?}",
+            invoked,
+            cmd.user_id,
+            cmd.user_name,
+            cmd.team_id,
+            cmd.channel_id,
+            self.roles,
+            held
+        );
+        Flow::Respond(Some(
+            Response::ephemeral(self.message.replace("{}", &invoked)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ids::UserId;
+    use testing::command;
+
+    #[test]
+    fn resolves_static_roles() {
+        let roles = StaticRoles::from_json(
+            r#"{
+                "users": { "U1": ["admin"] },
+                "usergroups": { "S1": { "members": ["U1", "U2"], "roles": ["oncall"] } }
+            }"#,
+        ).unwrap();
+        let cmd = |user: &str| command("/ops").user(user).build();
+        assert_eq!(roles.roles(&cmd("U1")), vec!["admin", "oncall"]);
+        assert_eq!(roles.roles(&cmd("U2")), vec!["oncall"]);
+        assert!(roles.roles(&cmd("U3")).is_empty());
+        let mut built = StaticRoles::new();
+        built.user(UserId::new("U1").unwrap(), vec!["admin"]).usergroup(
+            UserGroupId::new("S1").unwrap(),
+            UserGroup {
+                members: vec![UserId::new("U1").unwrap(), UserId::new("U2").unwrap()],
+                roles: vec!["oncall".to_owned()],
+            },
+        );
+        assert_eq!(built, roles);
+        assert!(StaticRoles::from_json(r#"{ "users": { "alice": ["admin"] } }"#).is_err());
+        assert!(
+            StaticRoles::from_json(r#"{ "usergroups": { "S1": { "members": ["oncall"] } } }"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn looks_up_usergroup_members() {
+        let lookups = Arc::new(Mutex::new(vec![]));
+        let looked_up = lookups.clone();
+        let roles = StaticRoles::from_json(
+            r#"{
+                "users": { "U1": ["admin"] },
+                "usergroups": {
+                    "S2": { "roles": ["oncall"] },
+                    "S1": { "members": ["U3"], "roles": ["deployer"] }
+                }
+            }"#,
+        ).unwrap()
+            .with_members(move |usergroup: &UserGroupId| {
+                looked_up.lock().unwrap().push(usergroup.to_string());
+                if usergroup == "S1" {
+                    return Err("unavailable".into());
+                }
+                Ok(vec![UserId::new("U1").unwrap(), UserId::new("U2").unwrap()])
+            });
+        let cmd = |user: &str| command("/ops").user(user).build();
+        assert_eq!(roles.roles(&cmd("U1")), vec!["admin", "oncall"]);
+        assert_eq!(roles.roles(&cmd("U2")), vec!["oncall"]);
+        assert_eq!(roles.roles(&cmd("U3")), vec!["deployer"]);
+        // failed lookups are cached as well as successful ones
+        assert_eq!(*lookups.lock().unwrap(), vec!["S1", "S2"]);
+    }
+}
@@ -1,6 +1,6 @@
 
 /// A payload to reply to commands with
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, PartialEq)]
 pub struct Response {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
@@ -9,7 +9,7 @@ pub struct Response {
     pub attachments: Vec<Attachment>,
 }
 
-#[derive(Debug, Default, Serialize, Clone)]
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
 pub struct Attachment {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
@@ -46,15 +46,60 @@ pub struct Attachment {
 
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub fields: Vec<Field>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
 }
 
-#[derive(Debug, Default, Serialize, Clone)]
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
 pub struct Field {
     pub title: String,
     pub value: String,
     pub short: bool,
 }
 
+/// An interactive button presented with an attachment.
+/// see https://api.slack.com/docs/message-buttons
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub struct Action {
+    pub name: String,
+    pub text: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+}
+
+impl Action {
+    /// returns a button which reports its name and value when clicked
+    pub fn button<N, T, V>(name: N, text: T, value: V) -> Action
+    where
+        N: Into<String>,
+        T: Into<String>,
+        V: Into<String>,
+    {
+        Action {
+            name: name.into(),
+            text: text.into(),
+            kind: "button".to_owned(),
+            value: Some(value.into()),
+            style: None,
+        }
+    }
+
+    /// styles the button, i.e. `primary` or `danger`
+    pub fn style<S>(mut self, style: S) -> Action
+    where
+        S: Into<String>,
+    {
+        self.style = Some(style.into());
+        self
+    }
+}
 
 impl Response {
     /// returned a response which will displayed for the user that issued the command
@@ -169,6 +214,9 @@ pub struct AttachmentBuilder {
     ts: Option<usize>,
 
     fields: Vec<Field>,
+
+    callback_id: Option<String>,
+    actions: Vec<Action>,
 }
 
 impl AttachmentBuilder {
@@ -282,6 +330,19 @@ impl AttachmentBuilder {
         self
     }
 
+    pub fn callback_id<S>(&mut self, id: S) -> &mut AttachmentBuilder
+    where
+        S: Into<String>,
+    {
+        self.callback_id = Some(id.into());
+        self
+    }
+
+    pub fn action(&mut self, a: Action) -> &mut AttachmentBuilder {
+        self.actions.push(a);
+        self
+    }
+
     pub fn build(&self) -> Attachment {
         Attachment {
             text: self.text.clone(),
@@ -304,6 +365,9 @@ impl AttachmentBuilder {
 
             ts: self.ts.clone(),
             fields: self.fields.iter().cloned().collect(),
+
+            callback_id: self.callback_id.clone(),
+            actions: self.actions.iter().cloned().collect(),
         }
     }
 }
@@ -0,0 +1,232 @@
+//! Declarative subcommand routing, i.e. `/ops deploy` and `/ops rollback`
+
+use super::{Command, Context, Handler, Params, RequestInfo, Responder};
+use response::Response;
+
+/// A named subcommand and its target Handler
+pub struct SubCommand {
+    name: String,
+    aliases: Vec<String>,
+    usage: String,
+    description: String,
+    handler: Box<Handler>,
+}
+
+impl SubCommand {
+    /// Adds an alternative name this subcommand may be invoked with
+    pub fn alias<A>(&mut self, alias: A) -> &mut SubCommand
+    where
+        A: Into<String>,
+    {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    /// returns the subcommand name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// returns the alternative names for this subcommand
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// returns a description of this subcommand's arguments, i.e. `<service> [--force]`
+    pub fn usage(&self) -> &str {
+        &self.usage
+    }
+
+    /// returns a description of what this subcommand does
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+/// A Handler which dispatches to subcommands based on the first word of a
+/// command's text. The matched word is removed from the text the subcommand's
+/// handler sees. Typing `help`, nothing, or an unknown subcommand replies with
+/// an ephemeral listing of subcommands
+#[derive(Default)]
+pub struct SubCommands {
+    subcommands: Vec<SubCommand>,
+}
+
+impl SubCommands {
+    pub fn new() -> SubCommands {
+        SubCommands { ..Default::default() }
+    }
+
+    /// Install a subcommand with a usage string, description, and target Handler
+    pub fn command<N, U, D, H>(
+        &mut self,
+        name: N,
+        usage: U,
+        description: D,
+        handler: H,
+    ) -> &mut SubCommand
+    where
+        N: Into<String>,
+        U: Into<String>,
+        D: Into<String>,
+        H: Handler + 'static,
+    {
+        self.subcommands.push(SubCommand {
+            name: name.into(),
+            aliases: vec![],
+            usage: usage.into(),
+            description: description.into(),
+            handler: Box::new(handler),
+        });
+        let last = self.subcommands.len() - 1;
+        &mut self.subcommands[last]
+    }
+
+    /// returns the installed subcommands
+    pub fn subcommands(&self) -> &[SubCommand] {
+        &self.subcommands
+    }
+
+    /// Returns an ephemeral response listing subcommands of the provided command
+    pub fn help(&self, command: &str) -> Response {
+        let mut lines = vec![format!("*{}* subcommands", command)];
+        for sub in self.subcommands.iter() {
+            let mut line = format!("`{} {}", command, sub.name);
+            if !sub.usage.is_empty() {
+                line.push(' ');
+                line.push_str(&sub.usage);
+            }
+            line.push('`');
+            if !sub.description.is_empty() {
+                line.push_str(" - ");
+                line.push_str(&sub.description);
+            }
+            if !sub.aliases.is_empty() {
+                line.push_str(&format!(" (aliases: {})", sub.aliases.join(", ")));
+            }
+            lines.push(line);
+        }
+        lines.push(format!("`{} help` - lists these subcommands", command));
+        Response::ephemeral(lines.join("\n"))
+    }
+}
+
+/// splits text into its first word and the remaining text
+fn split(text: &str) -> (&str, &str) {
+    let text = text.trim_left();
+    match text.find(char::is_whitespace) {
+        Some(idx) => (&text[..idx], text[idx..].trim_left()),
+        _ => (text, ""),
+    }
+}
+
+impl Handler for SubCommands {
+    fn handle(
+        &self,
+        cmd: &Command,
+        params: &Params,
+        responder: Box<Responder>,
+    ) -> Option<Response> {
+        let request = RequestInfo::default();
+        self.handle_context(Context::new(cmd, params, responder, &request))
+    }
+
+    fn handle_context(&self, ctx: Context) -> Option<Response> {
+        let cmd = ctx.command;
+        let (name, rest) = split(&cmd.text);
+        if name.is_empty() || name == "help" {
+            return Some(self.help(&cmd.command));
+        }
+        match self.subcommands.iter().find(|sub| sub.is_named(name)) {
+            Some(sub) => {
+                debug!("dispatching {} to subcommand {}", cmd.command, sub.name);
+                let sub_cmd = Command {
+                    text: rest.to_owned(),
+                    ..cmd.clone()
+                };
+                let params = ctx.params;
+                sub.handler.handle_context(ctx.with_command(&sub_cmd, params))
+            }
+            _ => {
+                let mut help = self.help(&cmd.command);
+                help.text = help.text.map(|text| {
+                    format!("Unknown subcommand `{}`\n{}", name, text)
+                });
+                Some(help)
+            }
+        }
+    }
+
+    fn as_handler(&self) -> &Handler {
+        self
+    }
+
+    fn subcommands(&self) -> Vec<String> {
+        self.subcommands
+            .iter()
+            .flat_map(|sub| Some(&sub.name).into_iter().chain(sub.aliases.iter()))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{NoopResponder, command};
+
+    fn ops() -> SubCommands {
+        let mut ops = SubCommands::new();
+        ops.command(
+            "deploy",
+            "<service>",
+            "deploys a service",
+            |cmd: &Command, _: &Params, _: Box<Responder>| {
+                Some(Response::ephemeral(format!("deploying {}", cmd.text)))
+            },
+        ).alias("d");
+        ops.command(
+            "rollback",
+            "",
+            "rolls back the last deploy",
+            |_: &Command, _: &Params, _: Box<Responder>| {
+                Some(Response::ephemeral("rolling back"))
+            },
+        );
+        ops
+    }
+
+    fn text(ops: &SubCommands, text: &str) -> String {
+        let cmd = command("/ops").text(text).build();
+        ops.handle(&cmd, &Params::new(), Box::new(NoopResponder))
+            .and_then(|res| res.text)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn dispatches_to_subcommands() {
+        let ops = ops();
+        assert_eq!(text(&ops, "deploy api  now"), "deploying api  now");
+        assert_eq!(text(&ops, " d api"), "deploying api");
+        assert_eq!(text(&ops, "rollback"), "rolling back");
+    }
+
+    #[test]
+    fn replies_with_help() {
+        let ops = ops();
+        let help = text(&ops, "help");
+        assert_eq!(
+            help,
+            "*/ops* subcommands\n\
+             `/ops deploy <service>` - deploys a service (aliases: d)\n\
+             `/ops rollback` - rolls back the last deploy\n\
+             `/ops help` - lists these subcommands"
+        );
+        assert_eq!(text(&ops, ""), help);
+        assert_eq!(text(&ops, "deploi api"), format!("Unknown subcommand `deploi`\n{}", help));
+    }
+}